@@ -7,18 +7,25 @@
 //! `luthien-plugin` provides plugin input data structures which can be deserialized with Serde, as well
 //! as a utility function to get the input from stdin.
 //!
+//! ## Responses
+//! Plugins can report their status, messages, and the files they wrote back to Luthien by
+//! emitting a [`Response`] on stdout.
+//!
 //! ## Luthien IO
 //! Luthien provides a named pipe which copies to and from its stdout and stdin respectively.
 //! `luthien-plugin` can automatically get this pipe for you.
 
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::path::PathBuf;
 
+pub mod response;
 pub mod theme;
 
 #[cfg(feature = "io")]
 pub mod io;
 
+pub use response::Response;
 pub use theme::{Colors, Palette, Theme};
 
 pub use serde_json;
@@ -58,9 +65,23 @@ pub struct Directories {
     pub data: PathBuf,
 }
 
+/// Version of the plugin protocol implemented by this library.
+///
+/// Version 0 is the original protocol, whose input has no `protocol_version` field.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Protocol versions this library can read input from.
+pub const SUPPORTED_PROTOCOL_VERSIONS: [u32; 2] = [0, PROTOCOL_VERSION];
+
+/// Argument Luthien passes to a plugin to ask which protocol versions it supports.
+pub const PROBE_FLAG: &str = "--luthien-protocol";
+
 /// All data passed to the plugin.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Input {
+    /// Version of the protocol Luthien is speaking, or [`None`] for version 0.
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
     #[serde(rename = "pipe")]
     pub pipe_path: Option<PathBuf>,
     /// Directories which can be used to store data between runs.
@@ -74,7 +95,68 @@ pub struct Input {
     pub theme: Theme,
 }
 
-/// Get the plugin's input or [`None`] if it couldn't be deserialized.
-pub fn get_input() -> Result<Input, serde_json::Error> {
-    serde_json::from_reader(&mut std::io::stdin())
+/// Why the plugin's input couldn't be read.
+#[derive(Debug)]
+pub enum InputError {
+    /// The input isn't valid JSON, or doesn't match [`Input`].
+    Malformed(serde_json::Error),
+    /// Luthien is speaking a protocol version which this library doesn't support.
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Malformed(err) => write!(
+                f,
+                "Input was malformed ({}). Try updating this plugin and/or Luthien.",
+                err
+            ),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Luthien is using protocol version {}, but this plugin only supports versions {:?}. Try updating this plugin.",
+                version, SUPPORTED_PROTOCOL_VERSIONS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Malformed(err) => Some(err),
+            Self::UnsupportedVersion(_) => None,
+        }
+    }
+}
+
+/// Get the plugin's input from stdin.
+///
+/// If Luthien is probing the plugin's supported protocol versions, this answers the probe and
+/// exits the process instead.
+pub fn get_input() -> Result<Input, InputError> {
+    if std::env::args().skip(1).any(|arg| arg == PROBE_FLAG) {
+        answer_probe()
+    }
+
+    read_input(std::io::stdin())
+}
+
+/// Read the plugin's input, checking that its protocol version is supported.
+pub fn read_input<R: std::io::Read>(reader: R) -> Result<Input, InputError> {
+    let input: Input = serde_json::from_reader(reader).map_err(InputError::Malformed)?;
+
+    match input.protocol_version.unwrap_or(0) {
+        version if SUPPORTED_PROTOCOL_VERSIONS.contains(&version) => Ok(input),
+        version => Err(InputError::UnsupportedVersion(version)),
+    }
+}
+
+/// Print the protocol versions supported by this library to stdout and exit.
+pub fn answer_probe() -> ! {
+    println!(
+        "{}",
+        serde_json::json!({ "protocol_versions": SUPPORTED_PROTOCOL_VERSIONS })
+    );
+    std::process::exit(0)
 }
@@ -0,0 +1,112 @@
+//! # Plugin Responses
+//!
+//! Plugins can report their results to Luthien by printing a [`Response`] to stdout as a single
+//! line of JSON. Luthien logs the response's messages and warnings, records the files it lists,
+//! and treats a [`Status::Failure`] as a failed run even if the plugin exits successfully.
+
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+pub use crate::PROTOCOL_VERSION;
+
+/// Whether the plugin succeeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Success,
+    Failure,
+}
+
+/// Severity of a [`Message`], corresponding to Luthien's log levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// A message to be logged by Luthien.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Message {
+    pub level: Level,
+    pub text: String,
+}
+
+/// The result of running the plugin, reported to Luthien.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Response {
+    /// Should always be [`PROTOCOL_VERSION`].
+    pub protocol_version: u32,
+    pub status: Status,
+    #[serde(default)]
+    pub messages: Vec<Message>,
+    /// Files written by the plugin.
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+impl Response {
+    /// An empty response with the given status.
+    pub fn new(status: Status) -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            status,
+            messages: Vec::new(),
+            files: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// An empty successful response.
+    pub fn success() -> Self {
+        Self::new(Status::Success)
+    }
+
+    /// An empty failed response.
+    pub fn failure() -> Self {
+        Self::new(Status::Failure)
+    }
+
+    /// Add a message to be logged at the given level.
+    pub fn message(mut self, level: Level, text: impl Into<String>) -> Self {
+        self.messages.push(Message {
+            level,
+            text: text.into(),
+        });
+        self
+    }
+
+    /// Add a file which the plugin wrote.
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.files.push(path.into());
+        self
+    }
+
+    /// Add a warning.
+    pub fn warning(mut self, text: impl Into<String>) -> Self {
+        self.warnings.push(text.into());
+        self
+    }
+
+    /// Print the response to stdout so that Luthien can read it.
+    pub fn emit(&self) -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+
+        serde_json::to_writer(&mut stdout, self)?;
+        writeln!(stdout)?;
+        stdout.flush()
+    }
+}
+
+impl Default for Response {
+    fn default() -> Self {
+        Self::success()
+    }
+}
@@ -6,6 +6,7 @@ use std::path::PathBuf;
 macro_rules! test_json {
     () => {
         json!({
+          "protocol_version": 1,
           "pipe": "/tmp/luthien_plugin_stdio_12345",
           "directories": {
             "config": "/home/user/.config/luthien/plugins/test",
@@ -52,13 +53,14 @@ macro_rules! test_json {
               "background": { "red": 0.0, "green": 0.0, "blue": 0.0 }
             }
           }
-        });
+        })
     };
 }
 
 macro_rules! test_correct {
     () => {
         Input {
+            protocol_version: Some(1),
             pipe_path: Some(PathBuf::from("/tmp/luthien_plugin_stdio_12345")),
             directories: Directories {
                 config: PathBuf::from("/home/user/.config/luthien/plugins/test"),
@@ -92,3 +94,37 @@ fn parse() {
 
     assert_eq!(input, test_correct!(),);
 }
+
+#[test]
+fn parse_unversioned() {
+    let mut json = test_json!();
+    json.as_object_mut().unwrap().remove("protocol_version");
+    let input: Input = serde_json::from_value(json).unwrap();
+
+    assert_eq!(
+        input,
+        Input {
+            protocol_version: None,
+            ..test_correct!()
+        }
+    );
+}
+
+#[test]
+fn unsupported_version() {
+    let mut json = test_json!();
+    json["protocol_version"] = json!(PROTOCOL_VERSION + 1);
+    let bytes = serde_json::to_vec(&json).unwrap();
+
+    assert!(matches!(
+        read_input(bytes.as_slice()),
+        Err(InputError::UnsupportedVersion(version)) if version == PROTOCOL_VERSION + 1
+    ));
+    assert!(matches!(
+        read_input(&b"{}"[..]),
+        Err(InputError::Malformed(_))
+    ));
+
+    let bytes = serde_json::to_vec(&test_json!()).unwrap();
+    assert_eq!(read_input(bytes.as_slice()).unwrap(), test_correct!());
+}
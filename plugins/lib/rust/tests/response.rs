@@ -0,0 +1,31 @@
+use luthien_plugin::response::*;
+use serde_json::json;
+use std::path::PathBuf;
+
+#[test]
+fn serialize() {
+    let response = Response::success()
+        .message(Level::Info, "Set colors in 3 terminals.")
+        .file("/home/user/.local/share/luthien/outputs/terminal/sequences")
+        .warning("Couldn't write to /dev/pts/4");
+
+    assert_eq!(
+        serde_json::to_value(&response).unwrap(),
+        json!({
+            "protocol_version": PROTOCOL_VERSION,
+            "status": "success",
+            "messages": [{ "level": "info", "text": "Set colors in 3 terminals." }],
+            "files": ["/home/user/.local/share/luthien/outputs/terminal/sequences"],
+            "warnings": ["Couldn't write to /dev/pts/4"]
+        })
+    );
+}
+
+#[test]
+fn parse_minimal() {
+    let response: Response =
+        serde_json::from_value(json!({ "protocol_version": 1, "status": "failure" })).unwrap();
+
+    assert_eq!(response, Response::failure());
+    assert_eq!(response.files, Vec::<PathBuf>::new());
+}
@@ -12,10 +12,7 @@ struct SassTemplate {
 impl From<luthien_plugin::Theme> for SassTemplate {
     fn from(theme: luthien_plugin::Theme) -> Self {
         Self {
-            wallpaper: theme
-                .wallpaper
-                .map(|p| p.to_str().map(String::from))
-                .flatten(),
+            wallpaper: theme.wallpaper.and_then(|p| p.to_str().map(String::from)),
             colors: theme.colors,
         }
     }
@@ -37,8 +34,10 @@ impl AsHexCode for luthien_plugin::palette::Srgb {
 }
 
 fn main() -> io::Result<()> {
-    let input = luthien_plugin::get_input()
-        .expect("Input was malformed. Try updating this plugin and/or Luthien.");
+    let input = luthien_plugin::get_input().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1)
+    });
 
     fs::write(
         input.directories.output.join("luthien.scss"),
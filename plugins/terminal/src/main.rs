@@ -1,3 +1,4 @@
+use luthien_plugin::response::{Level, Response};
 use luthien_plugin::{palette::Srgb, Palette, Theme};
 use std::fs;
 use std::io;
@@ -70,15 +71,18 @@ impl ControlSequence for Theme {
 }
 
 fn main() -> io::Result<()> {
-    let input = luthien_plugin::get_input()
-        .expect("Input was malformed. Try updating this plugin and/or Luthien.");
+    let input = luthien_plugin::get_input().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1)
+    });
 
     #[cfg(not(target_family = "unix"))]
     panic!("This plugin only works on Unixish systems.");
 
     let sequence = input.theme.sequence();
 
-    fs::write(input.directories.output.join("sequences"), &sequence)?;
+    let sequences_path = input.directories.output.join("sequences");
+    fs::write(&sequences_path, &sequence)?;
 
     let mut count = 0;
     for entry in fs::read_dir("/dev/pts")? {
@@ -88,6 +92,8 @@ fn main() -> io::Result<()> {
         }
     }
 
-    eprintln!("Set colors in {} terminals.", count);
-    Ok(())
+    Response::success()
+        .message(Level::Info, format!("Set colors in {} terminals.", count))
+        .file(sequences_path)
+        .emit()
 }
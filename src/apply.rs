@@ -1,12 +1,20 @@
-use crate::persist::{Config, Paths};
-use crate::plugin::Plugin;
+use crate::history;
+use crate::persist::{
+    Config, FailurePolicy, Paths, PluginConfig, PluginKind, ProtocolConfig, ProtocolKeyword,
+};
+use crate::plugin::{self, Exit, Output, Plugin};
+use crate::plugins;
 use crate::theme::Theme;
 use color_eyre::eyre::{eyre, Result, WrapErr};
-use log::{debug, error, info, trace, warn};
+use log::{error, info, trace, warn};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs::{self, File};
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
 use std::thread;
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
 
 #[derive(Debug, PartialEq, Clone, StructOpt)]
@@ -22,27 +30,260 @@ impl crate::Command for Opt {
     }
 }
 
-pub fn apply(config: &Config, theme: Theme) -> Result<()> {
+/// Re-apply the current theme, e.g. after changing plugins or their templates.
+#[derive(Debug, PartialEq, Clone, StructOpt)]
+pub struct ReapplyOpt {}
+
+impl crate::Command for ReapplyOpt {
+    fn run(self, paths: &Paths, _config: &Config) -> Result<Option<Theme>> {
+        info!("Reading the current theme...");
+        Ok(Some(last_applied(paths)?))
+    }
+}
+
+/// Which plugins to run when applying a theme.
+///
+/// Transform plugins are always run unless excluded with `--except`, so that the applied theme
+/// doesn't depend on which other plugins are selected.
+#[derive(Debug, Default, PartialEq, Clone, StructOpt)]
+pub struct Selection {
+    /// Only run the plugin with this name. Can be repeated.
+    #[structopt(long = "only", number_of_values = 1)]
+    only: Vec<String>,
+
+    /// Don't run the plugin with this name. Can be repeated.
+    #[structopt(long = "except", number_of_values = 1)]
+    except: Vec<String>,
+
+    /// Only run plugins with this tag. Can be repeated.
+    #[structopt(long = "tag", number_of_values = 1)]
+    tags: Vec<String>,
+}
+
+impl Selection {
+    /// Whether the plugin should be run.
+    fn includes(&self, plugin: &PluginConfig) -> bool {
+        let name = plugin.name();
+        if self.except.contains(&name) {
+            return false;
+        }
+
+        plugin.kind == PluginKind::Transform
+            || ((self.only.is_empty() || self.only.contains(&name))
+                && (self.tags.is_empty() || plugin.tags.iter().any(|t| self.tags.contains(t))))
+    }
+
+    /// Warn about plugin names which don't match any plugin.
+    fn check(&self, plugins: &[PluginConfig]) {
+        for name in self.only.iter().chain(self.except.iter()) {
+            if !plugins.iter().any(|pl| &pl.name() == name) {
+                warn!("No enabled plugin named {}", name);
+            }
+        }
+    }
+}
+
+pub fn apply(
+    paths: &Paths,
+    config: &Config,
+    selection: &Selection,
+    on_failure: FailurePolicy,
+    theme: Theme,
+) -> Result<()> {
     fn get_pipe() -> ipipe::Result<ipipe::Pipe> {
-        let pipe_path =
-            std::env::temp_dir().join(format!("luthien_plugin_stdio_{}", std::process::id()));
-        ipipe::Pipe::open(&pipe_path, ipipe::OnCleanup::Delete)
+        ipipe::Pipe::open(&pipe_path(), ipipe::OnCleanup::Delete)
     }
 
     info!("Applying theme...");
 
-    let output_dir = dirs::data_local_dir()
-        .ok_or_else(|| eyre!("Failed to get local data directory"))?
-        .join("luthien")
-        .join("outputs");
+    check_wallpaper(&theme)?;
+    let output_dir = output_dir()?;
+
+    trace!("Spawning plugin IO pipes...");
+    thread::spawn(|| io::copy(&mut io::stdin(), &mut get_pipe()?));
+    thread::spawn(|| io::copy(&mut get_pipe()?, &mut io::stdout()));
+
+    let pipe_path = get_pipe()
+        .map(|p| p.path().to_path_buf())
+        .map_err(|_| warn!("Failed to get a named pipe for the plugins"))
+        .ok();
+
+    // Transforms are run again whenever a theme is re-applied, so the original theme is recorded.
+    let original = theme.clone();
+    let plan = Plan::new(paths, config, selection)?;
+    let reports = run_plugins(plan, config, on_failure, theme, pipe_path, |theme| {
+        write_outputs(&output_dir, theme, &original)?;
+        history::record(
+            &paths.history,
+            &original,
+            config.apply.history.unwrap_or(history::DEFAULT_LENGTH),
+        )
+        .wrap_err("Failed to record the theme in history")
+    })?;
+
+    if !reports.is_empty() {
+        info!("Plugin summary:\n{}", Summary(&reports));
+    }
+
+    drop(get_pipe().wrap_err("Failed to create a named pipe for plugin IO")?);
+
+    outcome(&reports, on_failure)
+}
+
+/// Run the transforms in order, then `write` the transformed theme, then run the sinks, each as
+/// soon as its dependencies are done.
+///
+/// Nothing is written if a transform fails under the fail-fast policy.
+fn run_plugins(
+    plan: Plan,
+    config: &Config,
+    on_failure: FailurePolicy,
+    mut theme: Theme,
+    pipe_path: Option<PathBuf>,
+    write: impl FnOnce(&Theme) -> Result<()>,
+) -> Result<Vec<PluginReport>> {
+    let Plan {
+        plugins,
+        deps,
+        transforms,
+        sinks,
+    } = plan;
+
+    let mut reports = Vec::new();
+    // Whether each plugin failed or was skipped, once it's done.
+    let mut failed: Vec<Option<bool>> = vec![None; plugins.len()];
+    // Set when a plugin fails under the fail-fast policy, after which no more plugins are started.
+    let mut cancelled = false;
+
+    if !transforms.is_empty() {
+        info!("Running transform plugins...");
+    }
+    for i in transforms {
+        let pl = &plugins[i];
+        let mut report = match failed_dependency(&deps[i], &failed) {
+            _ if cancelled => PluginReport::skipped(pl, CANCELLED.into()),
+            Some(dep) => {
+                PluginReport::skipped(pl, format!("its dependency {} failed", plugins[dep].name()))
+            }
+            None => PluginReport::run(pl, &theme, pipe_path.as_ref(), pl.timeout(&config.apply)),
+        };
+        match report.theme.take() {
+            Some(transformed) => theme = transformed,
+            None if cancelled => {}
+            None => warn!("Skipping transform {}; the theme is unchanged", report.name),
+        }
+        failed[i] = Some(!report.succeeded());
+        cancelled |= on_failure == FailurePolicy::FailFast && !report.succeeded();
+        reports.push(report);
+    }
+
+    if !cancelled {
+        write(&theme)?;
+    }
+
+    info!("Running plugins...");
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config.apply.concurrency.unwrap_or(0))
+        .build()
+        .wrap_err("Failed to create plugin thread pool")?;
+    let (done_tx, done_rx) = mpsc::channel();
+    let mut waiting = sinks;
+    let mut running = 0;
+    loop {
+        // Start every plugin whose dependencies are done, repeating while skipped plugins
+        // unblock others.
+        let mut changed = true;
+        while changed {
+            changed = false;
+            waiting.retain(|&i| {
+                let pl = &plugins[i];
+                if cancelled {
+                    reports.push(PluginReport::skipped(pl, CANCELLED.into()));
+                    failed[i] = Some(true);
+                    return false;
+                }
+                if deps[i].iter().any(|&d| failed[d].is_none()) {
+                    return true;
+                }
+
+                if let Some(dep) = failed_dependency(&deps[i], &failed) {
+                    reports.push(PluginReport::skipped(
+                        pl,
+                        format!("its dependency {} failed", plugins[dep].name()),
+                    ));
+                    failed[i] = Some(true);
+                    changed = true;
+                } else {
+                    let (pl, theme, pipe_path, done_tx) = (
+                        pl.clone(),
+                        theme.clone(),
+                        pipe_path.clone(),
+                        done_tx.clone(),
+                    );
+                    let timeout = pl.timeout(&config.apply);
+                    pool.spawn(move || {
+                        let report = PluginReport::run(&pl, &theme, pipe_path.as_ref(), timeout);
+                        let _ = done_tx.send((i, report));
+                    });
+                    running += 1;
+                }
+                false
+            });
+        }
+
+        if running == 0 {
+            break;
+        }
+        let (i, report) = done_rx.recv().wrap_err("Failed to receive plugin report")?;
+        running -= 1;
+        failed[i] = Some(!report.succeeded());
+        cancelled |= on_failure == FailurePolicy::FailFast && !report.succeeded();
+        reports.push(report);
+    }
+
+    Ok(reports)
+}
+
+/// Whether applying the theme failed, given the plugins' reports.
+fn outcome(reports: &[PluginReport], on_failure: FailurePolicy) -> Result<()> {
+    let failures = reports
+        .iter()
+        .filter(|report| !report.skipped && !report.succeeded())
+        .count();
+    match on_failure {
+        _ if failures == 0 => Ok(()),
+        FailurePolicy::Continue => {
+            warn!("{} plugin(s) failed", failures);
+            Ok(())
+        }
+        FailurePolicy::FailAtEnd | FailurePolicy::FailFast => {
+            Err(eyre!("{} plugin(s) failed", failures))
+        }
+    }
+}
+
+/// Reason given for plugins which aren't run under the fail-fast policy.
+const CANCELLED: &str = "an earlier plugin failed";
+
+/// Write the theme and wallpaper to the application output directory, along with the theme from
+/// before any transforms so that it can be applied again.
+fn write_outputs(output_dir: &Path, theme: &Theme, original: &Theme) -> Result<()> {
+    // Checked again, since a transform may have changed the wallpaper.
+    check_wallpaper(theme)?;
 
     trace!("Writing theme to application output directory...");
     serde_json::to_writer_pretty(
         &mut File::create(output_dir.join("theme.json"))
             .wrap_err("Failed to create theme output file")?,
-        &theme,
+        theme,
     )
     .wrap_err("Failed to write theme to application output")?;
+    serde_json::to_writer_pretty(
+        &mut File::create(output_dir.join(SOURCE_FILE))
+            .wrap_err("Failed to create source theme file")?,
+        original,
+    )
+    .wrap_err("Failed to write source theme to application output")?;
 
     if let Some(path) = &theme.wallpaper {
         trace!("Copying wallpaper to application output directory...");
@@ -57,35 +298,587 @@ pub fn apply(config: &Config, theme: Theme) -> Result<()> {
         }
     }
 
-    trace!("Spawning plugin IO pipes...");
-    thread::spawn(|| io::copy(&mut io::stdin(), &mut get_pipe()?));
-    thread::spawn(|| io::copy(&mut get_pipe()?, &mut io::stdout()));
+    Ok(())
+}
 
-    info!("Running plugins...");
-    for pl in config.plugins.iter() {
-        trace!("Running plugin {}...", pl.name());
-        match pl.run(
-            theme.clone(),
-            get_pipe()
-                .map(|p| p.path().to_path_buf())
-                .map_err(|_| warn!("Failed to get a named pipe for the plugin"))
-                .ok(),
-        ) {
-            Ok(status) => {
-                if status.success() {
-                    info!("Plugin {} exited successfully", pl.name());
+/// Fail if the theme's wallpaper doesn't exist, before anything is written or run.
+fn check_wallpaper(theme: &Theme) -> Result<()> {
+    match &theme.wallpaper {
+        Some(path) if !path.is_file() => {
+            Err(eyre!("The wallpaper {} doesn't exist", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Write everything [`apply`] would do to `out`, without running plugins or writing any files.
+pub fn dry_run(
+    out: &mut impl Write,
+    paths: &Paths,
+    config: &Config,
+    selection: &Selection,
+    theme: Theme,
+) -> Result<()> {
+    let output_dir = output_dir()?;
+    let plan = Plan::new(paths, config, selection)?;
+
+    writeln!(
+        out,
+        "Would write the theme to {}, and the theme before any transforms to {}",
+        output_dir.join("theme.json").display(),
+        output_dir.join(SOURCE_FILE).display()
+    )?;
+    match &theme.wallpaper {
+        Some(path) if !path.is_file() => writeln!(
+            out,
+            "Would fail, because the wallpaper {} doesn't exist",
+            path.display()
+        )?,
+        Some(path) => writeln!(
+            out,
+            "Would copy the wallpaper from {} to {}",
+            path.display(),
+            output_dir.join("wallpaper").display()
+        )?,
+        None if output_dir.join("wallpaper").exists() => writeln!(
+            out,
+            "Would remove the old wallpaper at {}",
+            output_dir.join("wallpaper").display()
+        )?,
+        None => {}
+    }
+    if config.apply.history != Some(0) {
+        writeln!(out, "Would record the theme in {}", paths.history.display())?;
+    }
+
+    if plan.plugins.is_empty() {
+        writeln!(out, "Would run no plugins")?;
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "Would run {} plugin(s), in this order:",
+        plan.plugins.len()
+    )?;
+    if !plan.transforms.is_empty() {
+        writeln!(
+            out,
+            "(Transforms aren't run, so each input shows the theme before any transforms.)"
+        )?;
+    }
+
+    let pipe_path = pipe_path();
+    for &i in plan.transforms.iter().chain(plan.sinks.iter()) {
+        let pl = &plan.plugins[i];
+        let input = pl.input(theme.clone(), Some(&pipe_path), pl.protocol_version(false)?)?;
+        // The same precedence as when the plugin is run: its configured variables win.
+        let mut env: BTreeMap<_, _> = pl.standard_env(&input).into_iter().collect();
+        env.extend(pl.env.clone());
+        let input = input.to_json()?;
+
+        writeln!(out)?;
+        let kind = match pl.kind {
+            PluginKind::Sink => "sink",
+            PluginKind::Transform => "transform",
+        };
+        writeln!(out, "{} ({})", pl.name(), kind)?;
+        writeln!(out, "  executable: {}", pl.executable.display())?;
+        writeln!(out, "  args: {:?}", pl.args)?;
+        writeln!(out, "  env:")?;
+        for (key, value) in env {
+            writeln!(out, "    {}={}", key, value)?;
+        }
+        if !pl.after.is_empty() {
+            writeln!(out, "  after: {:?}", pl.after)?;
+        }
+        if let Some(timeout) = pl.timeout(&config.apply) {
+            writeln!(out, "  timeout: {:.1}s", timeout.as_secs_f32())?;
+        }
+        if pl.protocol == Some(ProtocolConfig::Keyword(ProtocolKeyword::Probe)) {
+            writeln!(
+                out,
+                "  protocol: would be probed; assuming the latest version"
+            )?;
+        }
+        writeln!(
+            out,
+            "  input: {}",
+            serde_json::to_string_pretty(&input)
+                .wrap_err("Failed to serialize plugin input")?
+                .replace('\n', "\n  ")
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Path of the named pipe which plugins can use to interact with the terminal.
+fn pipe_path() -> PathBuf {
+    std::env::temp_dir().join(format!("luthien_plugin_stdio_{}", std::process::id()))
+}
+
+/// Directory which the theme and wallpaper are written to.
+fn output_dir() -> Result<PathBuf> {
+    plugin::outputs_dir().ok_or_else(|| eyre!("Failed to get local data directory"))
+}
+
+/// Name of the file in the application output directory which holds the last applied theme as it
+/// was before any transforms.
+const SOURCE_FILE: &str = "source.json";
+
+/// The theme which was last applied.
+pub fn current_theme() -> Result<Theme> {
+    read_output("theme.json")?.ok_or_else(|| eyre!("No theme has been applied yet"))
+}
+
+/// The theme which was last applied, from before any transforms.
+///
+/// Unlike [`current_theme`], this can be applied again without applying transforms twice. Themes
+/// applied by older versions of Luthien have no source file, so the history is used instead.
+pub fn last_applied(paths: &Paths) -> Result<Theme> {
+    if let Some(theme) = read_output(SOURCE_FILE)? {
+        return Ok(theme);
+    }
+
+    match history::entries(&paths.history)?.first() {
+        Some(entry) => entry.theme(),
+        None => current_theme(),
+    }
+}
+
+/// Read a theme from the application output directory, if it exists.
+fn read_output(name: &str) -> Result<Option<Theme>> {
+    let file = match File::open(output_dir()?.join(name)) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).wrap_err_with(|| format!("Failed to read {}", name)),
+    };
+
+    serde_json::from_reader(io::BufReader::new(file))
+        .map(Some)
+        .wrap_err_with(|| format!("Failed to deserialize {}", name))
+}
+
+/// The plugins to run, and the order to run them in.
+struct Plan {
+    plugins: Vec<PluginConfig>,
+    /// Indices of each plugin's dependencies.
+    deps: Vec<Vec<usize>>,
+    /// Transform plugins, which are run one at a time in this order.
+    transforms: Vec<usize>,
+    /// Other plugins, in an order in which each comes after its dependencies.
+    sinks: Vec<usize>,
+}
+
+impl Plan {
+    fn new(paths: &Paths, config: &Config, selection: &Selection) -> Result<Self> {
+        let resolved = plugins::resolve(paths, config);
+        let names: Vec<_> = resolved.iter().map(|pl| pl.config.name()).collect();
+        let configs: Vec<_> = resolved.iter().map(|pl| pl.config.clone()).collect();
+        plugins::check_dependencies(&configs, &names)?;
+        // Discovered plugins weren't known when the configuration was validated.
+        plugins::check_transforms(&configs, &plugins::dependencies(&configs))?;
+
+        let enabled: Vec<_> = resolved
+            .into_iter()
+            .filter(|pl| pl.enabled)
+            .map(|pl| pl.config)
+            .collect();
+        selection.check(&enabled);
+        let plugins: Vec<_> = enabled
+            .into_iter()
+            .filter(|pl| selection.includes(pl))
+            .collect();
+
+        let deps = plugins::dependencies(&plugins);
+        let order = plugins::topological_order(&deps)
+            .map_err(|_| eyre!("Plugin dependencies form a cycle"))?;
+        let (transforms, sinks) = order
+            .into_iter()
+            .partition(|&i| plugins[i].kind == PluginKind::Transform);
+
+        Ok(Self {
+            plugins,
+            deps,
+            transforms,
+            sinks,
+        })
+    }
+}
+
+/// The first of a plugin's dependencies which failed or was skipped.
+fn failed_dependency(deps: &[usize], failed: &[Option<bool>]) -> Option<usize> {
+    deps.iter().copied().find(|&d| failed[d] == Some(true))
+}
+
+/// Outcome of running a single plugin.
+struct PluginReport {
+    name: String,
+    result: Result<Output>,
+    duration: Duration,
+    /// Theme written by a successful transform plugin.
+    theme: Option<Theme>,
+    /// Whether the plugin wasn't run because another plugin failed.
+    skipped: bool,
+}
+
+impl PluginReport {
+    fn run(
+        pl: &PluginConfig,
+        theme: &Theme,
+        pipe_path: Option<&PathBuf>,
+        timeout: Option<Duration>,
+    ) -> Self {
+        let name = pl.name();
+
+        trace!("Running plugin {}...", name);
+        let start = Instant::now();
+        let mut result = pl.run(theme.clone(), pipe_path, timeout);
+        let duration = start.elapsed();
+
+        let mut transformed = None;
+        if pl.kind == PluginKind::Transform {
+            if let Ok(output) = &result {
+                if output.success() {
+                    match output.theme() {
+                        Ok(theme) => transformed = Some(theme),
+                        Err(e) => result = Err(e),
+                    }
+                }
+            }
+        }
+
+        match &result {
+            Ok(output) => {
+                // Include the end of stderr so failures can be diagnosed from the error alone.
+                let stderr = if output.stderr_tail.is_empty() {
+                    String::new()
                 } else {
-                    error!("Plugin {} exited with a non-zero error code", pl.name());
+                    format!(":\n{}", output.stderr_tail.join("\n"))
+                };
+
+                match output.exit {
+                    _ if output.success() => info!("Plugin {} exited successfully", name),
+                    Exit::Status(status) if status.success() => {
+                        error!("Plugin {} reported a failure{}", name, stderr)
+                    }
+                    Exit::Status(status) => error!(
+                        "Plugin {} exited with a non-zero error code ({}){}",
+                        name, status, stderr
+                    ),
+                    Exit::TimedOut(timeout) => error!(
+                        "Plugin {} timed out after {:.1}s and was terminated{}",
+                        name,
+                        timeout.as_secs_f32(),
+                        stderr
+                    ),
                 }
             }
-            Err(e) => {
-                // TODO: Add descriptive error message
-                error!("Failed to run plugin {}.", pl.name());
-                debug!("{}", e);
+            Err(e) => error!("Failed to run plugin {}: {:#}", name, e),
+        }
+
+        Self {
+            name,
+            result,
+            duration,
+            theme: transformed,
+            skipped: false,
+        }
+    }
+
+    fn skipped(pl: &PluginConfig, reason: String) -> Self {
+        let name = pl.name();
+        warn!("Skipping plugin {} because {}", name, reason);
+
+        Self {
+            name,
+            result: Err(eyre!("Skipped because {}", reason)),
+            duration: Duration::default(),
+            theme: None,
+            skipped: true,
+        }
+    }
+
+    /// Whether the plugin ran and succeeded.
+    fn succeeded(&self) -> bool {
+        matches!(&self.result, Ok(output) if output.success())
+    }
+
+    fn status(&self) -> String {
+        if self.skipped {
+            return "skipped".into();
+        }
+
+        match self
+            .result
+            .as_ref()
+            .map(|output| (output.success(), output.exit))
+        {
+            Ok((true, _)) => "ok".into(),
+            Ok((false, Exit::Status(status))) if status.success() => "failed (reported)".into(),
+            Ok((false, Exit::Status(status))) => match status.code() {
+                Some(code) => format!("failed (exit code {})", code),
+                None => "failed (terminated by signal)".into(),
+            },
+            Ok((false, Exit::TimedOut(_))) => "timed out".into(),
+            Err(_) => "error".into(),
+        }
+    }
+}
+
+/// Table of plugin names, statuses, and durations.
+struct Summary<'a>(&'a [PluginReport]);
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name_width = self.0.iter().map(|r| r.name.len()).max().unwrap_or(0);
+        let statuses: Vec<_> = self.0.iter().map(PluginReport::status).collect();
+        let status_width = statuses.iter().map(String::len).max().unwrap_or(0);
+
+        for (i, (report, status)) in self.0.iter().zip(statuses.iter()).enumerate() {
+            if i != 0 {
+                writeln!(f)?;
             }
+            write!(
+                f,
+                "  {:name_width$}  {:status_width$}  {:.2}s",
+                report.name,
+                status,
+                report.duration.as_secs_f32(),
+                name_width = name_width,
+                status_width = status_width,
+            )?;
         }
+
+        Ok(())
     }
+}
 
-    drop(get_pipe().wrap_err("Failed to create a named pipe for plugin IO")?);
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, Home, TempDir};
+
+    fn plugin(name: &str, kind: &str, tags: &[&str]) -> PluginConfig {
+        toml::from_str(&format!(
+            "executable = \"/bin/true\"\nname = \"{}\"\nkind = \"{}\"\ntags = {:?}\n",
+            name, kind, tags
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn selection() {
+        let sass = plugin("sass", "sink", &["desktop"]);
+        let templates = plugin("templates", "sink", &["desktop", "editor"]);
+        let boost = plugin("boost", "transform", &[]);
+
+        let only = Selection {
+            only: vec!["templates".into()],
+            ..Default::default()
+        };
+        assert!(!only.includes(&sass));
+        assert!(only.includes(&templates));
+        assert!(only.includes(&boost), "Transforms should always run");
+
+        let tagged = Selection {
+            tags: vec!["editor".into()],
+            except: vec!["boost".into()],
+            ..Default::default()
+        };
+        assert!(!tagged.includes(&sass));
+        assert!(tagged.includes(&templates));
+        assert!(!tagged.includes(&boost));
+    }
+
+    /// A `[[plugins]]` entry, with any other fields in `extra`.
+    fn entry(name: &str, executable: &str, extra: &str) -> String {
+        format!(
+            "[[plugins]]\nname = \"{}\"\nexecutable = \"{}\"\n{}\n",
+            name, executable, extra
+        )
+    }
+
+    /// Run the plugins in the configuration, returning their reports, whether the theme was
+    /// written, and the outcome.
+    fn run(raw: &str, on_failure: FailurePolicy) -> (Vec<PluginReport>, bool, Result<()>) {
+        let _home = Home::new("apply");
+        let config: Config = toml::from_str(raw).unwrap();
+        let plan = Plan::new(&Paths::default(), &config, &Selection::default()).unwrap();
+
+        let mut written = false;
+        let reports = run_plugins(plan, &config, on_failure, test_util::theme(), None, |_| {
+            written = true;
+            Ok(())
+        })
+        .unwrap();
+        let outcome = outcome(&reports, on_failure);
+
+        (reports, written, outcome)
+    }
+
+    fn report<'a>(reports: &'a [PluginReport], name: &str) -> &'a PluginReport {
+        reports.iter().find(|report| report.name == name).unwrap()
+    }
+
+    #[test]
+    fn failure_policies() {
+        let raw = entry("fails", "/bin/false", "") + &entry("works", "/bin/true", "");
+
+        let (reports, written, outcome) = run(&raw, FailurePolicy::Continue);
+        assert!(written);
+        assert!(outcome.is_ok());
+        assert_eq!(report(&reports, "fails").status(), "failed (exit code 1)");
+        assert_eq!(report(&reports, "works").status(), "ok");
+
+        let (reports, written, outcome) = run(&raw, FailurePolicy::FailAtEnd);
+        assert!(written);
+        assert_eq!(outcome.unwrap_err().to_string(), "1 plugin(s) failed");
+        assert_eq!(report(&reports, "works").status(), "ok");
+
+        // Nothing is written or started after a transform fails.
+        let raw =
+            entry("fails", "/bin/false", "kind = \"transform\"") + &entry("works", "/bin/true", "");
+        let (reports, written, outcome) = run(&raw, FailurePolicy::FailFast);
+        assert!(!written);
+        assert!(outcome.is_err());
+        assert!(report(&reports, "works").skipped);
+
+        // Sinks which are already running finish, but no more are started.
+        let raw = entry("fails", "/bin/false", "")
+            + &entry("slow", "/bin/sh", "args = [\"-c\", \"sleep 0.2\"]")
+            + &entry("later", "/bin/true", "after = [\"slow\"]")
+            + "[apply]\nconcurrency = 2\n";
+        let (reports, _, outcome) = run(&raw, FailurePolicy::FailFast);
+        assert!(outcome.is_err());
+        assert_eq!(report(&reports, "slow").status(), "ok");
+        let later = report(&reports, "later");
+        assert!(later.skipped);
+        assert!(later
+            .result
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains(CANCELLED));
+    }
+
+    #[test]
+    fn concurrency() {
+        let sleep = |name| entry(name, "/bin/sh", "args = [\"-c\", \"sleep 0.3\"]");
+        let raw = sleep("a") + &sleep("b") + &sleep("c");
+        let elapsed = |concurrency: usize| {
+            let start = Instant::now();
+            let raw = format!("{}[apply]\nconcurrency = {}\n", raw, concurrency);
+            assert!(run(&raw, FailurePolicy::FailAtEnd).2.is_ok());
+            start.elapsed()
+        };
+
+        assert!(elapsed(1) >= Duration::from_millis(900));
+        assert!(elapsed(3) < Duration::from_millis(900));
+    }
+
+    #[test]
+    fn output_capture() {
+        let raw = entry(
+            "noisy",
+            "/bin/sh",
+            "args = [\"-c\", \"echo out; echo err >&2; exit 3\"]",
+        );
+        let (reports, _, _) = run(&raw, FailurePolicy::Continue);
+        let output = reports[0].result.as_ref().unwrap();
+
+        assert_eq!(output.stdout, "out\n");
+        assert_eq!(output.stderr_tail, vec!["err"]);
+        assert!(Summary(&reports)
+            .to_string()
+            .contains("noisy  failed (exit code 3)"));
+    }
+
+    #[test]
+    fn dependency_failure() {
+        let raw = entry("fails", "/bin/false", "")
+            + &entry("after-fails", "/bin/true", "after = [\"fails\"]")
+            + &entry("after-skipped", "/bin/true", "after = [\"after-fails\"]")
+            + &entry("independent", "/bin/true", "");
+        let (reports, _, outcome) = run(&raw, FailurePolicy::FailAtEnd);
+
+        assert_eq!(reports.len(), 4);
+        assert!(report(&reports, "after-fails").skipped);
+        assert!(report(&reports, "after-skipped").skipped);
+        assert_eq!(report(&reports, "independent").status(), "ok");
+        // Skipped plugins aren't counted as failures.
+        assert_eq!(outcome.unwrap_err().to_string(), "1 plugin(s) failed");
+    }
+
+    #[test]
+    fn transform_after_discovered_sink() {
+        let dir = TempDir::new("discovered_sink");
+        let sink = dir.join("luthien-sink");
+        fs::write(&sink, "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&sink, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let paths = Paths {
+            config: dir.join("config.toml"),
+            themes: dir.join("themes"),
+            cache: dir.join("cache"),
+            plugins: dir.to_path_buf(),
+            history: dir.join("history"),
+        };
+
+        let raw = entry(
+            "boost",
+            "/bin/true",
+            "kind = \"transform\"\nafter = [\"sink\"]",
+        ) + "[apply]\ndiscover = true\n";
+        let config: Config = toml::from_str(&raw).unwrap();
+        // The sink can't be known until it's discovered.
+        config.validate().unwrap();
+
+        match Plan::new(&paths, &config, &Selection::default()) {
+            Err(err) => assert!(
+                err.to_string()
+                    .contains("Transform plugin boost can't run after sink"),
+                "{}",
+                err
+            ),
+            Ok(_) => panic!("A transform was allowed to run after a discovered sink"),
+        }
+    }
+
+    #[test]
+    fn dry_run() {
+        let home = Home::new("dry_run");
+        let config: Config = toml::from_str(&entry(
+            "show",
+            "/bin/cat",
+            "env = { LUTHIEN_COLOR_RED = \"#123456\" }",
+        ))
+        .unwrap();
+
+        let mut out = Vec::new();
+        super::dry_run(
+            &mut out,
+            &Paths::default(),
+            &config,
+            &Selection::default(),
+            test_util::theme(),
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("Would fail, because the wallpaper test.jpg doesn't exist"));
+        assert!(out.contains("Would run 1 plugin(s)"));
+        assert!(out.contains("show (sink)\n  executable: /bin/cat\n"));
+        assert!(out.contains("    LUTHIEN_COLOR_RED=#123456\n"));
+        assert!(out.contains("    LUTHIEN_PLUGIN_NAME=show\n"));
+        assert!(out.contains("\"name\": \"show\""));
+        assert_eq!(
+            fs::read_dir(&*home).unwrap().count(),
+            0,
+            "A dry run shouldn't write anything"
+        );
+    }
 }
@@ -1,19 +1,20 @@
 use num_traits::{Float, Signed};
 use palette::{FromColor, IntoColor};
 use rayon::prelude::*;
-use std::ops::Add;
 
-pub fn lab_centroid<I, C, R>(iter: I) -> Option<R>
+/// Centroid of colors in the Lab color space, where each color contributes proportionally to its
+/// weight.
+pub fn weighted_lab_centroid<I, T, C, R>(iter: I) -> Option<R>
 where
-    I: IndexedParallelIterator,
-    I::Item: IntoColor<super::WhitePoint, C>,
+    I: ParallelIterator<Item = (T, C)>,
+    T: IntoColor<super::WhitePoint, C>,
     C: Send + palette::Component + Float + Signed,
     R: FromColor<super::WhitePoint, C>,
 {
-    let len = iter.len();
-    iter.map(IntoColor::into_lab)
-        .reduce_with(Add::add)
-        .map(|sum| sum / C::from(len).unwrap())
+    iter.map(|(col, weight)| (col.into_lab() * weight, weight))
+        .reduce_with(|(c1, w1), (c2, w2)| (c1 + c2, w1 + w2))
+        .filter(|(_, weight)| *weight > C::zero())
+        .map(|(sum, weight)| sum / weight)
         .map(FromColor::from_lab)
 }
 
@@ -23,22 +24,32 @@ mod tests {
     use rayon::prelude::*;
 
     #[test]
-    fn lab_centroid() {
+    fn weighted_lab_centroid() {
         assert_eq!(
-            super::lab_centroid(
-                [Lab::new(1.0, 2.0, 3.0), Lab::new(1.0, 2.0, 3.0)]
-                    .par_iter()
-                    .cloned()
+            super::weighted_lab_centroid(
+                [
+                    (Lab::new(1.0, 2.0, 3.0), 1.0),
+                    (Lab::new(2.0, 3.0, 4.0), 1.0)
+                ]
+                .par_iter()
+                .cloned()
             ),
-            Some(Lab::new(1.0, 2.0, 3.0))
+            Some(Lab::new(1.5, 2.5, 3.5))
         );
         assert_eq!(
-            super::lab_centroid(
-                [Lab::new(1.0, 2.0, 3.0), Lab::new(2.0, 3.0, 4.0)]
-                    .par_iter()
-                    .cloned()
+            super::weighted_lab_centroid(
+                [
+                    (Lab::new(1.0, 2.0, 3.0), 3.0),
+                    (Lab::new(5.0, 6.0, 7.0), 1.0)
+                ]
+                .par_iter()
+                .cloned()
             ),
-            Some(Lab::new(1.5, 2.5, 3.5))
+            Some(Lab::new(2.0, 3.0, 4.0))
+        );
+        assert_eq!(
+            super::weighted_lab_centroid::<_, Lab, f32, Lab>(rayon::iter::empty()),
+            None
         );
     }
 }
@@ -1,7 +1,7 @@
 pub mod average;
 pub mod region;
 
-pub use region::Region;
+pub use region::{Falloff, Region};
 
 // Many trait bounds require a set whitepoint. This is aliased here
 pub type WhitePoint = palette::white_point::D65;
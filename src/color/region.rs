@@ -11,6 +11,18 @@ where
     pub hue: mod_arith::Range<T, T>,
     pub saturation: RangeInclusive<T>,
     pub lightness: RangeInclusive<T>,
+    pub falloff: Falloff<T>,
+}
+
+/// Width of the soft edge outside each axis of a [`Region`].
+///
+/// Colors within the falloff width of a bound are given a membership weight which decreases
+/// linearly from one at the bound to zero at the edge of the falloff.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Falloff<T> {
+    pub hue: T,
+    pub saturation: T,
+    pub lightness: T,
 }
 
 impl<T> Region<T>
@@ -26,9 +38,19 @@ where
             hue: mod_arith::Space::new(T::from(360.0).unwrap()).range(*hue.start(), *hue.end()),
             saturation,
             lightness,
+            falloff: Falloff {
+                hue: T::zero(),
+                saturation: T::zero(),
+                lightness: T::zero(),
+            },
         }
     }
 
+    pub fn with_falloff(mut self, falloff: Falloff<T>) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
     pub fn contains<C>(&self, color: C) -> bool
     where
         C: IntoColor<super::WhitePoint, T>,
@@ -40,26 +62,53 @@ where
             && self.lightness.contains(&hsl.lightness)
     }
 
-    pub fn start(&self) -> Hsl<Srgb, T> {
-        Hsl::new(
-            self.hue.start(),
-            *self.saturation.start(),
-            *self.lightness.start(),
-        )
+    /// Degree to which a color belongs to the region, from zero to one.
+    pub fn membership<C>(&self, color: C) -> T
+    where
+        C: IntoColor<super::WhitePoint, T>,
+    {
+        fn axis<T: Float>(distance: T, falloff: T) -> T {
+            if distance <= T::zero() {
+                T::one()
+            } else if falloff <= T::zero() {
+                T::zero()
+            } else {
+                (T::one() - distance / falloff).max(T::zero())
+            }
+        }
+
+        fn linear_distance<T: Float>(range: &RangeInclusive<T>, n: T) -> T {
+            (*range.start() - n).max(n - *range.end()).max(T::zero())
+        }
+
+        let hsl = color.into_hsl::<Srgb>();
+
+        axis(self.hue.distance(hsl.hue.to_degrees()), self.falloff.hue)
+            * axis(
+                linear_distance(&self.saturation, hsl.saturation),
+                self.falloff.saturation,
+            )
+            * axis(
+                linear_distance(&self.lightness, hsl.lightness),
+                self.falloff.lightness,
+            )
     }
 
-    pub fn end(&self) -> Hsl<Srgb, T> {
+    /// The center of the region, used as a stand-in when no colors fall within it.
+    pub fn midpoint(&self) -> Hsl<Srgb, T> {
+        let two = T::from(2.0).unwrap();
+
         Hsl::new(
-            self.hue.length(),
-            *self.saturation.end(),
-            *self.lightness.end(),
+            self.hue.midpoint(),
+            (*self.saturation.start() + *self.saturation.end()) / two,
+            (*self.lightness.start() + *self.lightness.end()) / two,
         )
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Region;
+    use super::{Falloff, Region};
     use palette::{encoding::Srgb, Hsl, IntoColor};
 
     #[test]
@@ -75,4 +124,37 @@ mod tests {
 
         assert!(region.contains(Hsl::new(180.0, 0.5, 0.5).into_rgb::<Srgb>()));
     }
+
+    #[test]
+    fn region_membership() {
+        let region = Region::new(345.0..=15.0, 0.5..=1.0, 0.0..=1.0).with_falloff(Falloff {
+            hue: 10.0,
+            saturation: 0.25,
+            lightness: 0.0,
+        });
+
+        assert_eq!(region.membership(Hsl::new(0.0, 0.75, 0.5)), 1.0);
+        assert_eq!(region.membership(Hsl::new(20.0, 0.75, 0.5)), 0.5);
+        assert_eq!(region.membership(Hsl::new(340.0, 0.75, 0.5)), 0.5);
+        assert_eq!(region.membership(Hsl::new(90.0, 0.75, 0.5)), 0.0);
+        assert_eq!(region.membership(Hsl::new(0.0, 0.375, 0.5)), 0.5);
+        assert_eq!(region.membership(Hsl::new(0.0, 0.0, 0.5)), 0.0);
+
+        let hard = Region::new(0.0..=180.0, 0.0..=0.5, 0.0..=0.5);
+        assert_eq!(hard.membership(Hsl::new(90.0, 0.25, 0.25)), 1.0);
+        assert_eq!(hard.membership(Hsl::new(270.0, 0.25, 0.25)), 0.0);
+    }
+
+    #[test]
+    fn region_midpoint() {
+        let region = Region::new(345.0..=15.0, 0.5..=1.0, 0.1..=0.9);
+        let midpoint = region.midpoint();
+
+        assert_eq!(midpoint.hue.to_positive_degrees(), 0.0);
+        assert_eq!(midpoint.saturation, 0.75);
+        assert_eq!(midpoint.lightness, 0.5);
+
+        let region = Region::new(210.0..=255.0, 0.5..=1.0, 0.1..=0.9);
+        assert_eq!(region.midpoint().hue.to_positive_degrees(), 232.5);
+    }
 }
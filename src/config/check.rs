@@ -0,0 +1,410 @@
+use crate::mod_arith;
+use crate::persist::{self, Config, Paths};
+use crate::plugins;
+use crate::theme::Palette;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use toml::Spanned;
+
+/// Mirror of [`Config`] which keeps the location of each value that is validated.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Located {
+    plugins: Vec<LocatedPlugin>,
+    extractors: Vec<LocatedPlugin>,
+    apply: LocatedApply,
+    extraction: LocatedExtraction,
+}
+
+#[derive(Debug, Deserialize)]
+struct LocatedPlugin {
+    executable: Spanned<PathBuf>,
+    timeout: Option<Spanned<f64>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct LocatedApply {
+    timeout: Option<Spanned<f64>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct LocatedExtraction {
+    target: Option<Palette<LocatedRegion>>,
+    profiles: HashMap<String, LocatedProfile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct LocatedProfile {
+    target: Palette<Option<LocatedRegion>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LocatedRegion {
+    hue: Spanned<(f32, f32)>,
+    saturation: Spanned<(f32, f32)>,
+    lightness: Spanned<(f32, f32)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Diagnostic {
+    severity: Severity,
+    offset: Option<usize>,
+    message: String,
+}
+
+impl Diagnostic {
+    fn error<T>(at: &Spanned<T>, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            offset: Some(at.start()),
+            message,
+        }
+    }
+
+    fn warning<T>(at: &Spanned<T>, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            offset: Some(at.start()),
+            message,
+        }
+    }
+}
+
+pub fn check(paths: &Paths) -> Result<()> {
+    info!("Checking configuration file {}...", paths.config.display());
+
+    let raw = fs::read_to_string(&paths.config).wrap_err("Failed to read configuration file")?;
+
+    let config: Config = match toml::from_str(&raw) {
+        Ok(config) => config,
+        Err(err) => {
+            error!("{}: {}", paths.config.display(), err);
+            return Err(eyre!("Configuration file is invalid"));
+        }
+    };
+    let located: Located =
+        toml::from_str(&raw).wrap_err("Failed to locate values in configuration file")?;
+
+    let mut diagnostics = Vec::new();
+    if let Some(target) = &located.extraction.target {
+        diagnostics.extend(check_regions(target.as_ref().map(Some)));
+    }
+    for (name, profile) in located.extraction.profiles.iter() {
+        let target = profile.target.as_ref().map(Option::as_ref);
+        diagnostics.extend(check_regions(target).into_iter().map(|diag| Diagnostic {
+            message: format!("{} (in profile {})", diag.message, name),
+            ..diag
+        }));
+    }
+    diagnostics.extend(check_plugins(&config, &located));
+    if let Err(err) = config.validate_plugins() {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            offset: None,
+            message: format!("{:#}", err),
+        });
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|diag| diag.severity == Severity::Error)
+        .count();
+    for diag in diagnostics.iter() {
+        let location = match diag.offset {
+            Some(offset) => {
+                let (line, col) = line_col(&raw, offset);
+                format!("{}:{}:{}", paths.config.display(), line, col)
+            }
+            None => paths.config.display().to_string(),
+        };
+
+        match diag.severity {
+            Severity::Warning => warn!("{}: {}", location, diag.message),
+            Severity::Error => error!("{}: {}", location, diag.message),
+        }
+    }
+
+    if errors == 0 {
+        info!(
+            "Configuration is valid ({} warning(s))",
+            diagnostics.len() - errors
+        );
+        Ok(())
+    } else {
+        Err(eyre!("Configuration has {} error(s)", errors))
+    }
+}
+
+/// One-based line and column of a byte offset.
+fn line_col(raw: &str, offset: usize) -> (usize, usize) {
+    let before = &raw[..offset];
+    let line = before.matches('\n').count() + 1;
+    let col = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+
+    (line, col)
+}
+
+/// Check the given regions of a target; profiles may leave some out.
+fn check_regions(target: Palette<Option<&LocatedRegion>>) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    target.zip(Palette::NAMES).map(|(region, name)| {
+        let region = match region {
+            Some(region) => region,
+            None => return,
+        };
+        let (start, end) = *region.hue.get_ref();
+        if !(0.0..=360.0).contains(&start) || !(0.0..=360.0).contains(&end) {
+            diagnostics.push(Diagnostic::warning(
+                &region.hue,
+                format!(
+                    "hue of region {} ({}–{}) is outside of 0–360 and will wrap",
+                    name, start, end
+                ),
+            ));
+        }
+
+        for (axis, range) in [
+            ("saturation", &region.saturation),
+            ("lightness", &region.lightness),
+        ]
+        .iter()
+        {
+            let (start, end) = *range.get_ref();
+            if !(0.0..=1.0).contains(&start) || !(0.0..=1.0).contains(&end) {
+                diagnostics.push(Diagnostic::error(
+                    range,
+                    format!(
+                        "{} of region {} ({}–{}) is outside of 0–1",
+                        axis, name, start, end
+                    ),
+                ));
+            }
+            if start > end {
+                diagnostics.push(Diagnostic::error(
+                    range,
+                    format!(
+                        "{} of region {} is inverted ({} > {})",
+                        axis, name, start, end
+                    ),
+                ));
+            }
+        }
+    });
+
+    let space = mod_arith::Space::new(360.0);
+    let accents: Vec<_> = target
+        .zip(Palette::NAMES)
+        .accents()
+        .iter()
+        .filter_map(|&(region, name)| {
+            let (start, end) = *region?.hue.get_ref();
+            Some((name, &region?.hue, space.range(start, end)))
+        })
+        .collect();
+    // Gaps can't be found if some accent regions come from the default target.
+    let complete = accents.len() == 6;
+
+    for (i, (name, span, range)) in accents.iter().enumerate() {
+        if range.length() == 0.0 {
+            diagnostics.push(Diagnostic::warning(
+                span,
+                format!("hue of accent region {} spans the entire hue wheel", name),
+            ));
+            continue;
+        }
+
+        for (other_name, _, other) in accents.iter().skip(i + 1) {
+            if other.length() != 0.0
+                && (range.contains(other.start()) || other.contains(range.start()))
+            {
+                diagnostics.push(Diagnostic::warning(
+                    span,
+                    format!(
+                        "hue of region {} ({}–{}) overlaps region {} ({}–{})",
+                        name,
+                        range.start(),
+                        range.end(),
+                        other_name,
+                        other.start(),
+                        other.end()
+                    ),
+                ));
+            }
+        }
+
+        // There is a gap after this region unless another region continues past its end.
+        let covered = !complete
+            || accents
+                .iter()
+                .filter(|(other_name, _, other)| other_name != name && other.length() != 0.0)
+                .any(|(_, _, other)| other.contains(range.end()) && other.end() != range.end());
+        if !covered {
+            let next = accents
+                .iter()
+                .filter(|(other_name, _, other)| other_name != name && other.length() != 0.0)
+                .map(|(other_name, _, other)| {
+                    (space.dist_pos(range.end(), other.start()), other_name)
+                })
+                .min_by(|(d1, _), (d2, _)| d1.partial_cmp(d2).unwrap());
+
+            if let Some((distance, next_name)) = next {
+                diagnostics.push(Diagnostic::warning(
+                    span,
+                    format!(
+                        "no region covers hues {}–{} between regions {} and {}",
+                        range.end(),
+                        space.modulo(range.end() + distance),
+                        name,
+                        next_name
+                    ),
+                ));
+            }
+        }
+    }
+
+    diagnostics
+}
+
+fn check_plugins(config: &Config, located: &Located) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<_> = config
+        .plugins
+        .iter()
+        .zip(located.plugins.iter())
+        .chain(config.extractors.iter().zip(located.extractors.iter()))
+        .filter_map(|(plugin, located)| {
+            plugins::check_executable(&plugin.executable)
+                .err()
+                .map(|message| Diagnostic::error(&located.executable, message))
+        })
+        .collect();
+
+    let timeouts = located
+        .plugins
+        .iter()
+        .chain(located.extractors.iter())
+        .filter_map(|plugin| plugin.timeout.as_ref())
+        .chain(located.apply.timeout.as_ref());
+    for timeout in timeouts {
+        let secs = *timeout.get_ref();
+        if persist::check_timeout(secs).is_err() {
+            diagnostics.push(Diagnostic::error(
+                timeout,
+                format!("timeout must be a positive number of seconds, not {}", secs),
+            ));
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnose(raw: &str) -> Vec<Diagnostic> {
+        let located: Located = toml::from_str(raw).unwrap();
+        check_regions(located.extraction.target.unwrap().as_ref().map(Some))
+    }
+
+    fn region(name: &str, hue: (f32, f32), saturation: (f32, f32)) -> String {
+        format!(
+            "[extraction.target.{}]\nhue = [{:?}, {:?}]\nsaturation = [{:?}, {:?}]\nlightness = [0.0, 1.0]\n",
+            name, hue.0, hue.1, saturation.0, saturation.1
+        )
+    }
+
+    type RegionSpec<'a> = (&'a str, (f32, f32), (f32, f32));
+
+    fn target(regions: &[RegionSpec]) -> String {
+        let mut raw = String::new();
+        for &(name, hue, saturation) in regions {
+            raw.push_str(&region(name, hue, saturation));
+        }
+        raw
+    }
+
+    const DEFAULT_HUES: [(&str, (f32, f32)); 8] = [
+        ("black", (0.0, 360.0)),
+        ("red", (345.0, 15.0)),
+        ("green", (90.0, 150.0)),
+        ("yellow", (45.0, 75.0)),
+        ("blue", (210.0, 255.0)),
+        ("purple", (270.0, 300.0)),
+        ("cyan", (165.0, 195.0)),
+        ("white", (0.0, 360.0)),
+    ];
+
+    #[test]
+    fn line_col() {
+        assert_eq!(super::line_col("abc\ndef", 0), (1, 1));
+        assert_eq!(super::line_col("abc\ndef", 5), (2, 2));
+    }
+
+    #[test]
+    fn default_gaps() {
+        let raw = target(
+            &DEFAULT_HUES
+                .iter()
+                .map(|&(name, hue)| (name, hue, (0.0, 1.0)))
+                .collect::<Vec<_>>(),
+        );
+        let diagnostics = diagnose(&raw);
+
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+        assert_eq!(
+            diagnostics.len(),
+            6,
+            "Expected a gap after each accent:\n{:#?}",
+            diagnostics
+        );
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.message == "no region covers hues 15–45 between regions red and yellow"));
+    }
+
+    #[test]
+    fn overlap_and_invalid_ranges() {
+        let mut regions: Vec<_> = DEFAULT_HUES
+            .iter()
+            .map(|&(name, hue)| (name, hue, (0.0, 1.0)))
+            .collect();
+        regions[1] = ("red", (330.0, 100.0), (0.8, 0.2));
+        regions[3] = ("yellow", (45.0, 75.0), (0.0, 1.5));
+        let raw = target(&regions);
+        let diagnostics = diagnose(&raw);
+
+        let errors: Vec<_> = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect();
+        assert_eq!(errors.len(), 2, "{:#?}", errors);
+        assert!(errors[0].message.contains("inverted"));
+        assert!(errors[1].message.contains("outside of 0–1"));
+        assert_eq!(
+            super::line_col(&raw, errors[0].offset.unwrap()),
+            (7, 14),
+            "Error should point at red's saturation"
+        );
+
+        assert!(diagnostics.iter().any(|d| d
+            .message
+            .starts_with("hue of region red (330–100) overlaps region green")));
+        assert!(diagnostics.iter().any(|d| d
+            .message
+            .starts_with("hue of region red (330–100) overlaps region yellow")));
+    }
+}
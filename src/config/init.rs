@@ -0,0 +1,41 @@
+use crate::persist::{Config, Paths};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use log::info;
+use std::fs;
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, PartialEq, StructOpt)]
+pub struct Opt {
+    /// Overwrite an existing configuration file.
+    #[structopt(short, long)]
+    force: bool,
+}
+
+impl Opt {
+    pub fn run(self, paths: &Paths) -> Result<()> {
+        // Older versions of Luthien initialized the configuration as an empty file, and newer ones
+        // write the default configuration on first run, so neither counts as the user's config.
+        let default = Config::commented_default()?;
+        let is_blank = fs::read_to_string(&paths.config)
+            .map(|contents| contents.trim().is_empty() || contents == default)
+            .unwrap_or(true);
+
+        if !is_blank && !self.force {
+            return Err(eyre!(
+                "Configuration file {} already exists; use --force to overwrite it",
+                paths.config.display()
+            ));
+        }
+
+        info!(
+            "Writing default configuration to {}...",
+            paths.config.display()
+        );
+        if let Some(parent) = paths.config.parent() {
+            fs::create_dir_all(parent).wrap_err("Failed to create configuration directory")?;
+        }
+        fs::write(&paths.config, default).wrap_err("Failed to write configuration file")?;
+
+        Ok(())
+    }
+}
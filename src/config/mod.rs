@@ -0,0 +1,38 @@
+mod check;
+mod init;
+mod show;
+
+use crate::persist::Paths;
+use color_eyre::eyre::Result;
+use structopt::StructOpt;
+
+#[derive(Debug, PartialEq, Clone, StructOpt)]
+pub enum Opt {
+    /// Validate the configuration file.
+    ///
+    /// Reports syntax errors, invalid extraction regions, gaps and overlaps between regions
+    /// around the hue wheel, and missing or non-executable plugins.
+    Check,
+
+    /// Print the effective configuration.
+    ///
+    /// This includes default values and fully expanded plugin executable paths.
+    Show(show::Opt),
+
+    /// Write a commented default configuration file.
+    Init(init::Opt),
+}
+
+impl Opt {
+    /// Run the config command.
+    ///
+    /// Unlike other commands, this doesn't take a loaded [`Config`](crate::persist::Config),
+    /// because it must work with configuration files which fail to load.
+    pub fn run(self, paths: &Paths) -> Result<()> {
+        match self {
+            Self::Check => check::check(paths),
+            Self::Show(opt) => opt.run(paths),
+            Self::Init(opt) => opt.run(paths),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use crate::persist::Paths;
+use color_eyre::eyre::{Result, WrapErr};
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, PartialEq, StructOpt)]
+pub struct Opt {
+    /// Format to print the configuration in.
+    #[structopt(short, long, default_value = "toml", possible_values = &["toml", "json"])]
+    format: Format,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "toml" => Ok(Self::Toml),
+            "json" => Ok(Self::Json),
+            _ => Err("Invalid format"),
+        }
+    }
+}
+
+impl Opt {
+    pub fn run(self, paths: &Paths) -> Result<()> {
+        let config = paths
+            .get_config()
+            .wrap_err("Failed to load configuration")?;
+
+        match self.format {
+            Format::Toml => print!("{}", config.to_toml()?),
+            Format::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&config)
+                    .wrap_err("Failed to serialize configuration")?
+            ),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use crate::apply;
+use crate::persist::{Config, Paths};
+use crate::theme::{self, Theme};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use palette::Srgb;
+use serde_json::Value;
+use structopt::StructOpt;
+
+#[derive(Debug, PartialEq, Clone, StructOpt)]
+pub struct Opt {
+    /// Format to print the theme in.
+    #[structopt(
+        short,
+        long,
+        default_value = "json",
+        possible_values = &["json", "toml", "hex", "shell"]
+    )]
+    format: Format,
+
+    /// Only print the value at this path, like `colors.palette.red` or `colors.accents.0`.
+    #[structopt(short, long)]
+    get: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    Toml,
+    /// Hex codes, preceded by their path unless a single color is printed.
+    Hex,
+    /// Shell variable assignments, named like the environment variables given to plugins.
+    Shell,
+}
+
+impl std::str::FromStr for Format {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            "hex" => Ok(Self::Hex),
+            "shell" => Ok(Self::Shell),
+            _ => Err("Invalid format"),
+        }
+    }
+}
+
+impl crate::Command for Opt {
+    fn run(self, _paths: &Paths, _config: &Config) -> Result<Option<Theme>> {
+        let theme = apply::current_theme()?;
+        println!("{}", self.format(&theme)?);
+
+        Ok(None)
+    }
+}
+
+impl Opt {
+    fn format(&self, theme: &Theme) -> Result<String> {
+        let path: Vec<String> = match &self.get {
+            Some(path) => path.split('.').map(String::from).collect(),
+            None => Vec::new(),
+        };
+        let value = get(
+            &serde_json::to_value(theme).wrap_err("Failed to serialize the theme")?,
+            &path,
+        )
+        .ok_or_else(|| eyre!("The theme has no value at \"{}\"", path.join(".")))?
+        .clone();
+
+        Ok(match self.format {
+            Format::Json => {
+                serde_json::to_string_pretty(&value).wrap_err("Failed to serialize the theme")?
+            }
+            Format::Toml => toml::Value::try_from(without_nulls(value))
+                .and_then(|value| match value {
+                    toml::Value::Table(_) => toml::to_string(&value),
+                    value => Ok(value.to_string()),
+                })
+                .wrap_err("Failed to serialize the theme as TOML")?
+                .trim_end()
+                .into(),
+            Format::Hex => {
+                let mut leaves = Vec::new();
+                flatten(path.clone(), &value, &mut leaves);
+
+                match leaves.as_slice() {
+                    [(leaf_path, leaf)] if *leaf_path == path => leaf.clone(),
+                    _ => leaves
+                        .into_iter()
+                        .map(|(path, leaf)| format!("{} {}", path.join("."), leaf))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                }
+            }
+            Format::Shell => {
+                let mut leaves = Vec::new();
+                flatten(path, &value, &mut leaves);
+
+                leaves
+                    .into_iter()
+                    .map(|(path, leaf)| {
+                        format!("{}='{}'", variable_name(&path), leaf.replace('\'', "'\\''"))
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        })
+    }
+}
+
+/// The value at the path, where array elements are selected by their index.
+fn get<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(array) => array.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Collect the colors and other scalar values within `value`, along with their paths.
+///
+/// Colors are formatted as hex codes, and null values are skipped.
+fn flatten(path: Vec<String>, value: &Value, leaves: &mut Vec<(Vec<String>, String)>) {
+    if let Ok(color) = serde_json::from_value::<Srgb>(value.clone()) {
+        leaves.push((path, theme::hex(color)));
+        return;
+    }
+
+    let children: Vec<(String, &Value)> = match value {
+        Value::Null => return,
+        Value::String(s) => return leaves.push((path, s.clone())),
+        Value::Object(map) => map.iter().map(|(k, v)| (k.clone(), v)).collect(),
+        Value::Array(array) => array
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i.to_string(), v))
+            .collect(),
+        value => return leaves.push((path, value.to_string())),
+    };
+
+    for (key, child) in children {
+        let mut path = path.clone();
+        path.push(key);
+        flatten(path, child, leaves);
+    }
+}
+
+/// Name of the shell variable for the value at the path, like `LUTHIEN_COLOR_RED`.
+///
+/// Colors are named the same as the environment variables given to plugins.
+fn variable_name(path: &[String]) -> String {
+    let parts: Vec<&str> = match path.split_first() {
+        Some((first, rest)) if first == "colors" => std::iter::once("color")
+            .chain(rest.iter().filter_map(|part| match part.as_str() {
+                "palette" => None,
+                "accents" => Some("accent"),
+                part => Some(part),
+            }))
+            .collect(),
+        _ => path.iter().map(String::as_str).collect(),
+    };
+
+    std::iter::once("LUTHIEN")
+        .chain(parts)
+        .collect::<Vec<_>>()
+        .join("_")
+        .to_uppercase()
+}
+
+/// Remove null values from objects, since TOML can't represent them.
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, without_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(array) => Value::Array(array.into_iter().map(without_nulls).collect()),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    fn print(format: &str, get: Option<&str>) -> Result<String> {
+        let mut theme = test_util::theme();
+        theme.colors.accents = vec![Srgb::new(1.0, 0.5, 0.0)];
+
+        Opt {
+            format: format.parse().unwrap(),
+            get: get.map(String::from),
+        }
+        .format(&theme)
+    }
+
+    #[test]
+    fn format() {
+        let format = |f, get| print(f, get).unwrap();
+
+        assert_eq!(format("hex", Some("colors.accents.0")), "#ff8000");
+        assert_eq!(
+            format("hex", Some("colors.accents")),
+            "colors.accents.0 #ff8000"
+        );
+        assert_eq!(
+            format("shell", Some("colors.foreground")),
+            "LUTHIEN_COLOR_FOREGROUND='#ffffff'"
+        );
+        assert!(format("shell", None).contains("LUTHIEN_COLOR_ACCENT_0='#ff8000'\n"));
+        assert!(format("toml", None).contains("[colors.background]\nblue = 0.0\n"));
+        assert_eq!(
+            serde_json::from_str::<Value>(&format("json", Some("colors.background"))).unwrap(),
+            serde_json::to_value(Srgb::new(0.0, 0.0, 0.0)).unwrap()
+        );
+        assert!(print("json", Some("colors.accents.1")).is_err());
+    }
+}
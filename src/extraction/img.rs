@@ -1,13 +1,16 @@
 use super::{Extractor, HashResult};
 use crate::color::{average, Region, WhitePoint};
+use crate::mod_arith;
 use crate::persist::ExtractionConfig;
 use crate::theme::{Colors, Palette, Theme};
 use color_eyre::eyre::{Result, WrapErr};
-use log::{info, trace};
+use log::{info, trace, warn};
 use num_traits::{Float, Signed};
-use palette::{FromColor, IntoColor, Srgb};
+use palette::{encoding::Srgb as Encoding, FromColor, Hsl, IntoColor, Srgb};
 use rayon::prelude::*;
+use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
 use structopt::StructOpt;
 
 #[derive(Debug, Clone, PartialEq, StructOpt)]
@@ -41,7 +44,7 @@ impl std::str::FromStr for Preference {
 }
 
 impl Extractor for Opt {
-    fn hash<H: Hasher>(&self, _config: &ExtractionConfig, state: &mut H) -> Result<HashResult> {
+    fn hash<H: Hasher>(&self, config: &ExtractionConfig, state: &mut H) -> Result<HashResult> {
         let img = image::io::Reader::open(&self.path)
             .wrap_err("Failed to read image file")?
             .with_guessed_format()?
@@ -50,8 +53,13 @@ impl Extractor for Opt {
             .into_rgb8();
 
         img.hash(state);
+        // The theme's wallpaper is the image's path, so copies of the image can't share a theme.
+        self.path
+            .canonicalize()
+            .wrap_err("Failed to find image file")?
+            .hash(state);
         self.preference.hash(state);
-        // FIXME: Also hash extraction target.
+        config.target.hash(state);
 
         Ok(HashResult::Finished)
     }
@@ -87,40 +95,39 @@ impl Extractor for Opt {
 }
 
 impl Opt {
-    fn gen_palette<I, C, R>(&self, cols: I, regs: &Palette<Region<C>>) -> Palette<(R, usize)>
+    fn gen_palette<I, C, R>(&self, cols: I, regs: &Palette<Region<C>>) -> Palette<(R, C)>
     where
         I: Clone + ParallelIterator,
-        I::Item: Clone + IntoColor<WhitePoint, C>,
-        C: Send + Sync + palette::Component + Float + Signed,
+        I::Item: Clone + Send + IntoColor<WhitePoint, C>,
+        C: Send + Sync + palette::Component + Float + Signed + std::iter::Sum,
         R: Clone + FromColor<WhitePoint, C>,
     {
-        let split = regs.split(cols).map(IntoParallelIterator::into_par_iter);
+        let split = regs.split(cols);
         trace!("Averaging image colors...");
-        let partial = split
-            .map(|part| (part.len(), average::lab_centroid::<_, _, R>(part)))
-            .map(|(count, col)| (col, count));
-
-        let extrapolate = |targ: &Region<C>| {
-            R::from_lab((targ.start().into_lab() + targ.end().into_lab()) / C::from(2).unwrap())
-        };
+        let partial = split.map(|part| {
+            let weight = part.iter().map(|(_, w)| *w).sum();
+            let col = average::weighted_lab_centroid(part.into_par_iter());
+            (col, weight)
+        });
 
-        partial
-            .zip(regs.as_ref())
-            .map(|((col, count), reg)| (col.unwrap_or_else(|| extrapolate(reg)), count))
+        trace!("Synthesizing colors for empty regions...");
+        regs.fill_empty(partial.map(|(col, _)| col))
+            .zip(partial)
+            .map(|(col, (_, weight))| (R::from_hsl(col), weight))
     }
 
     fn gen_colors<I, C, R>(&self, cols: I, regs: Palette<Region<C>>) -> Colors<R>
     where
         I: Clone + ParallelIterator,
-        I::Item: Clone + IntoColor<WhitePoint, C>,
-        C: Send + Sync + palette::Component + Float + Signed,
+        I::Item: Clone + Send + IntoColor<WhitePoint, C>,
+        C: Send + Sync + palette::Component + Float + Signed + std::iter::Sum,
         R: Copy + FromColor<WhitePoint, C>,
     {
         let pal = self.gen_palette(cols, &regs);
 
         trace!("Finding and sorting accents...");
         let mut accents = pal.accents();
-        accents.sort_by(|(_, n1), (_, n2)| n2.cmp(n1));
+        accents.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap_or(Ordering::Equal));
 
         Colors {
             palette: pal.map(|(c, _)| c),
@@ -150,14 +157,88 @@ impl<C> Palette<Region<C>>
 where
     C: palette::Component + Float + Signed,
 {
-    fn split<I>(&self, iter: I) -> Palette<Vec<I::Item>>
+    /// Split colors into each region along with their membership weights, omitting colors which
+    /// are entirely outside of a region.
+    fn split<I>(&self, iter: I) -> Palette<Vec<(I::Item, C)>>
     where
-        C: Sync,
+        C: Send + Sync,
         I: Clone + ParallelIterator,
         I::Item: Clone + IntoColor<WhitePoint, C>,
     {
-        self.clone()
-            .map(|reg| iter.clone().filter(|c| reg.contains(c.clone())).collect())
+        self.clone().map(|reg| {
+            iter.clone()
+                .map(|c| {
+                    let weight = reg.membership(c.clone());
+                    (c, weight)
+                })
+                .filter(|(_, weight)| *weight > C::zero())
+                .collect()
+        })
+    }
+
+    /// Fill in colors for regions which no colors fell within.
+    ///
+    /// A synthesized color takes its hue from the midpoint of its region, and its saturation and
+    /// lightness from the nearest populated accents on either side of it around the hue wheel,
+    /// clamped to the region's bounds. Regions spanning the whole wheel use their midpoint.
+    fn fill_empty(
+        &self,
+        centroids: Palette<Option<Hsl<Encoding, C>>>,
+    ) -> Palette<Hsl<Encoding, C>> {
+        let mut missing = Vec::new();
+        centroids.as_ref().zip(Palette::NAMES).map(|(col, name)| {
+            if col.is_none() {
+                missing.push(name)
+            }
+        });
+        if !missing.is_empty() {
+            warn!(
+                "No colors found for {}; synthesized from region midpoints and neighboring colors",
+                missing.join(", ")
+            );
+        }
+
+        let space = mod_arith::Space::new(C::from(360.0).unwrap());
+        let populated: Vec<_> = centroids.accents().iter().flatten().copied().collect();
+        let nearest = |dist: &dyn Fn(C) -> C| {
+            populated.iter().min_by(|c1, c2| {
+                dist(c1.hue.to_positive_degrees())
+                    .partial_cmp(&dist(c2.hue.to_positive_degrees()))
+                    .unwrap_or(Ordering::Equal)
+            })
+        };
+
+        self.as_ref().zip(centroids).map(|(reg, col)| {
+            col.unwrap_or_else(|| {
+                let midpoint = reg.midpoint();
+                if reg.hue.length().is_zero() {
+                    return midpoint;
+                }
+
+                let hue = midpoint.hue.to_positive_degrees();
+                match (
+                    nearest(&|h| space.dist_pos(hue, h)),
+                    nearest(&|h| space.dist_pos(h, hue)),
+                ) {
+                    (Some(after), Some(before)) => {
+                        let two = C::from(2.0).unwrap();
+                        let clamp = |n: C, range: &RangeInclusive<C>| {
+                            n.max(*range.start()).min(*range.end())
+                        };
+
+                        Hsl::new(
+                            hue,
+                            clamp(
+                                (after.saturation + before.saturation) / two,
+                                &reg.saturation,
+                            ),
+                            clamp((after.lightness + before.lightness) / two, &reg.lightness),
+                        )
+                    }
+                    _ => midpoint,
+                }
+            })
+        })
     }
 }
 
@@ -178,16 +259,39 @@ mod tests {
         assert_eq!(
             regs.split([Hsl::new(0.0, 0.0, 0.0)].par_iter().cloned()),
             Palette {
-                black: vec![Hsl::new(0.0, 0.0, 0.0)],
+                black: vec![(Hsl::new(0.0, 0.0, 0.0), 1.0)],
                 ..Default::default()
             }
         );
         assert_eq!(
             regs.split([Hsl::new(0.0, 1.0, 0.5)].par_iter().cloned()),
             Palette {
-                red: vec![Hsl::new(0.0, 1.0, 0.5)],
+                red: vec![(Hsl::new(0.0, 1.0, 0.5), 1.0)],
                 ..Default::default()
             }
         );
     }
+
+    #[test]
+    fn fill_empty() {
+        use crate::persist;
+        use palette::Hsl;
+
+        let regs: Palette<color::Region<f32>> =
+            Palette::<persist::RegionConfig>::default().map(Into::into);
+
+        let filled = regs.fill_empty(Palette {
+            red: Some(Hsl::new(0.0, 1.0, 0.25)),
+            blue: Some(Hsl::new(232.5, 0.5, 0.75)),
+            ..Default::default()
+        });
+
+        assert_eq!(filled.red, Hsl::new(0.0, 1.0, 0.25));
+        assert_eq!(filled.blue, Hsl::new(232.5, 0.5, 0.75));
+        assert_eq!(filled.green, Hsl::new(120.0, 0.75, 0.5));
+        assert_eq!(filled.black, Hsl::new(0.0, 0.5, 0.05));
+
+        let filled = regs.fill_empty(Palette::default());
+        assert_eq!(filled.yellow, Hsl::new(60.0, 0.75, 0.5));
+    }
 }
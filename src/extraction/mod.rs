@@ -1,4 +1,5 @@
 mod img;
+mod plugin;
 mod toml;
 
 use crate::persist::{Config, ExtractionConfig, Paths};
@@ -7,7 +8,7 @@ use color_eyre::eyre::{Result, WrapErr};
 use log::{error, info, trace, warn};
 use std::collections::hash_map::DefaultHasher;
 use std::fs::File;
-use std::hash::Hasher;
+use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -17,6 +18,18 @@ pub struct Opt {
     #[structopt(long = "no-cache", parse(from_flag = std::ops::Not::not))]
     cache: bool,
 
+    /// Named extraction profile to use instead of the default.
+    #[structopt(short, long)]
+    profile: Option<String>,
+
+    /// Also save the theme to the theme library with this name.
+    #[structopt(long)]
+    save: Option<String>,
+
+    /// Replace an existing theme with the same name when saving.
+    #[structopt(long, requires = "save")]
+    force: bool,
+
     /// Which extractor should be used.
     #[structopt(subcommand)]
     extractor: Extractors,
@@ -27,10 +40,6 @@ pub trait Extractor {
     fn hash<H: Hasher>(&self, config: &ExtractionConfig, state: &mut H) -> Result<HashResult>;
 }
 
-#[impl_enum::with_methods {
-  fn extract(&self, config: &ExtractionConfig) -> Result<Theme> {}
-  fn hash<H: Hasher>(&self, config: &ExtractionConfig, state: &mut H) -> Result<HashResult> {}
-}]
 #[derive(Debug, PartialEq, Clone, StructOpt)]
 enum Extractors {
     /// Extract common colors from an image
@@ -40,41 +49,67 @@ enum Extractors {
     /// Manually create a theme.
     #[structopt(aliases = &["manual", "man"])]
     Toml(toml::Opt),
+
+    /// Extract a theme with an extractor plugin.
+    #[structopt(aliases = &["plug"])]
+    Plugin(plugin::Opt),
 }
 
-impl Extractors {
-    fn cache_path(&self, paths: &Paths, config: &Config) -> Result<Option<PathBuf>> {
-        let hash = {
-            let mut hasher = DefaultHasher::default();
-
-            if !self
-                .hash(&config.extraction, &mut hasher)
-                .wrap_err("Failed to generate caching ID for extraction")?
-                .finished()
-            {
-                return Ok(None);
-            }
+fn cache_path(
+    extractor: &impl Extractor,
+    paths: &Paths,
+    config: &ExtractionConfig,
+    profile: Option<&str>,
+) -> Result<Option<PathBuf>> {
+    let hash = {
+        let mut hasher = DefaultHasher::default();
+        profile.hash(&mut hasher);
+
+        if !extractor
+            .hash(config, &mut hasher)
+            .wrap_err("Failed to generate caching ID for extraction")?
+            .finished()
+        {
+            return Ok(None);
+        }
 
-            hasher.finish()
-        };
+        hasher.finish()
+    };
 
-        Ok(Some(paths.cache.join(format!("{:16x}", hash))))
-    }
+    Ok(Some(paths.cache.join(format!("{:16x}", hash))))
 }
 
 impl crate::Command for Opt {
     fn run(self, paths: &Paths, config: &Config) -> Result<Option<Theme>> {
+        match &self.extractor {
+            Extractors::Image(extractor) => self.extract(extractor, paths, config),
+            Extractors::Toml(extractor) => self.extract(extractor, paths, config),
+            Extractors::Plugin(extractor) => {
+                self.extract(&extractor.resolve(config)?, paths, config)
+            }
+        }
+    }
+}
+
+impl Opt {
+    fn extract(
+        &self,
+        extractor: &impl Extractor,
+        paths: &Paths,
+        config: &Config,
+    ) -> Result<Option<Theme>> {
+        let profile = self.profile.as_deref();
+        let extraction = config.extraction.profile(profile)?;
+
         trace!("Finding extraction cache location...");
-        let cache_path = self
-            .extractor
-            .cache_path(paths, config)
+        let cache_path = cache_path(extractor, paths, &extraction, profile)
             .wrap_err("Failed to find extraction cache location")?;
 
         info!("Extracting theme...");
-        // NOTE: This is necessary because let chains are not yet stable. In the future, `if let Some(cache_path) = cache_path && ...` should be possible.
-        let theme = if cache_path.is_some() && self.cache && cache_path.as_ref().unwrap().exists() {
-            let cache_path = cache_path.as_ref().unwrap();
-
+        let cached = cache_path
+            .as_ref()
+            .filter(|cache_path| self.cache && cache_path.exists());
+        let mut theme: Theme = if let Some(cache_path) = cached {
             info!("Cache hit; using cached theme...");
 
             serde_json::from_reader(
@@ -88,11 +123,13 @@ impl crate::Command for Opt {
                 info!("Extracting theme...");
             }
 
-            self.extractor
-                .extract(&config.extraction)
+            extractor
+                .extract(&extraction)
                 .wrap_err("Failed to extract theme")?
         };
 
+        theme.canonicalize_wallpaper()?;
+
         if let Some(cache_path) = cache_path {
             trace!("Caching extracted theme...");
             File::create(cache_path)
@@ -103,6 +140,11 @@ impl crate::Command for Opt {
                 .unwrap_or_else(|_| error!("Failed to create theme cache file"));
         }
 
+        if let Some(name) = &self.save {
+            let path = paths.save_theme(name, &theme, self.force)?;
+            info!("Saved theme to {}", path.display());
+        }
+
         Ok(Some(theme))
     }
 }
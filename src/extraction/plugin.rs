@@ -0,0 +1,140 @@
+use super::{Extractor, HashResult};
+use crate::persist::{Config, ExtractionConfig, PluginConfig};
+use crate::plugin::{plugin_env, Directories, Exit, Output, Plugin, PROTOCOL_VERSION};
+use crate::theme::Theme;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use log::{debug, trace};
+use serde::Serialize;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+use structopt::{clap::AppSettings, StructOpt};
+
+/// Extract a theme using an extractor plugin from the configuration file.
+///
+/// The plugin receives its options and the extraction configuration as JSON on stdin, and writes
+/// the theme as JSON to stdout.
+#[derive(Debug, Clone, PartialEq, StructOpt)]
+#[structopt(setting = AppSettings::TrailingVarArg)]
+pub struct Opt {
+    /// Name of the extractor plugin.
+    name: String,
+
+    /// Arguments passed on to the extractor plugin.
+    #[structopt(allow_hyphen_values = true)]
+    args: Vec<String>,
+}
+
+/// An extractor plugin found in the configuration, with the arguments to run it with.
+pub struct Resolved<'a> {
+    opt: &'a Opt,
+    plugin: &'a PluginConfig,
+    timeout: Option<Duration>,
+}
+
+/// Argument passed to extractor plugins to ask for a cache key instead of a theme.
+///
+/// Plugins which can't be cached should print nothing or exit unsuccessfully.
+const CACHE_KEY_FLAG: &str = "--luthien-cache-key";
+
+/// Data provided to the extractor plugin process through its stdin.
+#[derive(Debug, Serialize)]
+struct ExtractorInput<'a> {
+    protocol_version: u32,
+    directories: Directories,
+
+    name: String,
+    options: &'a serde_json::Value,
+    extraction: &'a ExtractionConfig,
+}
+
+impl Opt {
+    /// Find the extractor plugin in the configuration.
+    pub fn resolve<'a>(&'a self, config: &'a Config) -> Result<Resolved<'a>> {
+        let plugin = config
+            .extractors
+            .iter()
+            .find(|pl| pl.name() == self.name)
+            .ok_or_else(|| eyre!("No extractor plugin named \"{}\"", self.name))?;
+
+        Ok(Resolved {
+            opt: self,
+            plugin,
+            timeout: plugin.timeout(&config.apply),
+        })
+    }
+}
+
+impl Resolved<'_> {
+    /// Run the extractor plugin, failing unless it exits successfully.
+    fn execute(&self, config: &ExtractionConfig, flag: Option<&str>) -> Result<Output> {
+        let plugin = self.plugin;
+        let name = plugin.name();
+        let directories =
+            Directories::new(&name).ok_or_else(|| eyre!("Failed to find plugin directories"))?;
+        directories
+            .ensure_initialized()
+            .wrap_err("Failed to initialize plugin directories")?;
+        let env = plugin_env(&name, &directories);
+        let input = serde_json::to_value(ExtractorInput {
+            protocol_version: PROTOCOL_VERSION,
+            directories,
+            name,
+            options: &plugin.options,
+            extraction: config,
+        })
+        .wrap_err("Failed to serialize extractor input")?;
+
+        let args: Vec<&str> = flag
+            .into_iter()
+            .chain(self.opt.args.iter().map(String::as_str))
+            .collect();
+        let output = plugin.execute(&args, &env, &input, self.timeout, false)?;
+
+        match output.exit {
+            _ if output.success() => Ok(output),
+            Exit::Status(status) => Err(eyre!(
+                "Extractor plugin {} exited with a non-zero error code ({})",
+                self.opt.name,
+                status
+            )),
+            Exit::TimedOut(timeout) => Err(eyre!(
+                "Extractor plugin {} timed out after {:.1}s",
+                self.opt.name,
+                timeout.as_secs_f32()
+            )),
+        }
+    }
+}
+
+impl Extractor for Resolved<'_> {
+    fn extract(&self, config: &ExtractionConfig) -> Result<Theme> {
+        trace!("Running extractor plugin {}...", self.opt.name);
+        self.execute(config, None)?.theme()
+    }
+
+    fn hash<H: Hasher>(&self, config: &ExtractionConfig, state: &mut H) -> Result<HashResult> {
+        trace!(
+            "Asking extractor plugin {} for a cache key...",
+            self.opt.name
+        );
+        let key = match self.execute(config, Some(CACHE_KEY_FLAG)) {
+            Ok(output) if !output.stdout.trim().is_empty() => output.stdout,
+            _ => {
+                debug!(
+                    "Extractor plugin {} didn't supply a cache key",
+                    self.opt.name
+                );
+                return Ok(HashResult::Inapplicable);
+            }
+        };
+
+        self.plugin.executable.hash(state);
+        self.plugin.args.hash(state);
+        self.plugin.options.to_string().hash(state);
+        self.opt.args.hash(state);
+        key.trim().hash(state);
+        config.target.hash(state);
+
+        Ok(HashResult::Finished)
+    }
+}
@@ -62,7 +62,12 @@ impl Extractor for Opt {
             }
         };
         let source: Source = toml::from_slice(&bytes).wrap_err("Source was invalid.")?;
-        Ok(source.into())
+
+        let mut theme = Theme::from(source);
+        if let Some(dir) = self.path.as_ref().and_then(|path| path.parent()) {
+            theme.resolve_wallpaper(dir);
+        }
+        Ok(theme)
     }
 
     fn hash<H: Hasher>(&self, _: &ExtractionConfig, _: &mut H) -> Result<HashResult> {
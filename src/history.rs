@@ -0,0 +1,208 @@
+use crate::persist::{Config, Paths};
+use crate::theme::Theme;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use log::{info, trace};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use structopt::StructOpt;
+
+/// Number of applied themes remembered if [`ApplyConfig::history`] isn't set.
+///
+/// [`ApplyConfig::history`]: crate::persist::ApplyConfig::history
+pub const DEFAULT_LENGTH: usize = 20;
+
+#[derive(Debug, PartialEq, Clone, StructOpt)]
+pub struct Opt {}
+
+impl crate::Command for Opt {
+    fn run(self, paths: &Paths, _config: &Config) -> Result<Option<Theme>> {
+        let entries = entries(&paths.history)?;
+        if entries.is_empty() {
+            info!("No themes have been applied yet");
+            return Ok(None);
+        }
+
+        let now = SystemTime::now();
+        for (i, entry) in entries.iter().enumerate() {
+            let theme = entry.theme()?;
+            println!(
+                "{:>3}  {:>8}  {}  {}",
+                i,
+                age(now, entry.time),
+                theme.colors.palette,
+                theme
+                    .wallpaper
+                    .as_ref()
+                    .map_or_else(|| "(no wallpaper)".into(), |p| p.display().to_string()),
+            );
+        }
+
+        Ok(None)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, StructOpt)]
+pub struct RollbackOpt {
+    /// How many themes to go back, where 0 is the current theme.
+    #[structopt(default_value = "1")]
+    pub steps: usize,
+}
+
+impl crate::Command for RollbackOpt {
+    fn run(self, paths: &Paths, _config: &Config) -> Result<Option<Theme>> {
+        let entries = entries(&paths.history)?;
+        let entry = entries.get(self.steps).ok_or_else(|| {
+            eyre!(
+                "Can't go back {} theme(s); the history only has {}",
+                self.steps,
+                entries.len()
+            )
+        })?;
+
+        info!("Reading theme from history...");
+        Ok(Some(entry.theme()?))
+    }
+}
+
+/// A previously applied theme.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub time: SystemTime,
+}
+
+impl Entry {
+    pub fn theme(&self) -> Result<Theme> {
+        let file = File::open(&self.path).wrap_err("Failed to read theme from history")?;
+        serde_json::from_reader(io::BufReader::new(file))
+            .wrap_err("Failed to deserialize theme from history")
+    }
+}
+
+/// Applied themes, most recent first.
+pub fn entries(dir: &Path) -> Result<Vec<Entry>> {
+    let read = match fs::read_dir(dir) {
+        Ok(read) => read,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).wrap_err("Failed to read history directory"),
+    };
+
+    let mut entries: Vec<_> = read
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter_map(|path| {
+            let millis = path
+                .file_name()?
+                .to_str()?
+                .strip_suffix(".json")?
+                .parse()
+                .ok()?;
+            Some(Entry {
+                time: UNIX_EPOCH + Duration::from_millis(millis),
+                path,
+            })
+        })
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.time));
+
+    Ok(entries)
+}
+
+/// Add the theme to the history, then forget all but the `length` most recent themes.
+///
+/// Nothing is added if the theme is the same as the most recent one.
+pub fn record(dir: &Path, theme: &Theme, length: usize) -> Result<()> {
+    let mut entries = entries(dir)?;
+
+    if length > 0 && entries.first().and_then(|e| e.theme().ok()).as_ref() != Some(theme) {
+        trace!("Recording theme in history...");
+        fs::create_dir_all(dir).wrap_err("Failed to create history directory")?;
+
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        // Keep entries in order even if the clock went backwards.
+        let millis = entries
+            .first()
+            .and_then(|e| e.time.duration_since(UNIX_EPOCH).ok())
+            .map_or(millis, |last| millis.max(last.as_millis() + 1));
+        let path = dir.join(format!("{}.json", millis));
+
+        serde_json::to_writer_pretty(
+            File::create(&path).wrap_err("Failed to create history entry")?,
+            theme,
+        )
+        .wrap_err("Failed to write theme to history")?;
+        entries.insert(
+            0,
+            Entry {
+                path,
+                time: UNIX_EPOCH + Duration::from_millis(millis as u64),
+            },
+        );
+    }
+
+    for old in entries.iter().skip(length) {
+        trace!("Forgetting {} from history...", old.path.display());
+        fs::remove_file(&old.path).wrap_err("Failed to remove old history entry")?;
+    }
+
+    Ok(())
+}
+
+/// How long ago `time` was, like `5m ago`.
+fn age(now: SystemTime, time: SystemTime) -> String {
+    let secs = now.duration_since(time).unwrap_or_default().as_secs();
+
+    match secs {
+        0..=59 => "just now".into(),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, TempDir};
+
+    fn theme(wallpaper: &str) -> Theme {
+        Theme {
+            wallpaper: Some(PathBuf::from(wallpaper)),
+            ..test_util::theme()
+        }
+    }
+
+    #[test]
+    fn record() {
+        let root = TempDir::new("history");
+        let dir = root.join("history");
+
+        assert!(entries(&dir).unwrap().is_empty());
+        for wallpaper in &["a", "b", "b", "c", "d"] {
+            super::record(&dir, &theme(wallpaper), 3).unwrap();
+        }
+        let themes: Vec<_> = entries(&dir)
+            .unwrap()
+            .iter()
+            .map(|e| e.theme().unwrap())
+            .collect();
+
+        assert_eq!(themes, vec![theme("d"), theme("c"), theme("b")]);
+    }
+
+    #[test]
+    fn age() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let ago = |secs| super::age(now, now - Duration::from_secs(secs));
+
+        assert_eq!(ago(5), "just now");
+        assert_eq!(ago(150), "2m ago");
+        assert_eq!(ago(7200), "2h ago");
+        assert_eq!(ago(200_000), "2d ago");
+    }
+}
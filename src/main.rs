@@ -5,12 +5,19 @@ use structopt::{clap, StructOpt};
 
 mod apply;
 mod color;
+mod config;
+mod current;
 mod extraction;
+mod history;
 mod mod_arith;
 mod modify;
 mod persist;
 mod plugin;
+mod plugins;
+#[cfg(test)]
+mod test_util;
 mod theme;
+mod themes;
 
 use persist::{Config, Paths};
 use theme::Theme;
@@ -30,6 +37,17 @@ struct Opt {
     #[structopt(short, long)]
     output: Option<PathBuf>,
 
+    /// Print what applying the theme would do, without running plugins or writing files
+    #[structopt(long)]
+    dry_run: bool,
+
+    #[structopt(flatten)]
+    selection: apply::Selection,
+
+    /// What to do when a plugin fails. Overrides the config file.
+    #[structopt(long, possible_values = &["continue", "fail-at-end", "fail-fast"])]
+    on_failure: Option<persist::FailurePolicy>,
+
     #[structopt(subcommand)]
     command: Commands,
 }
@@ -40,6 +58,12 @@ enum Commands {
     #[structopt(aliases = &["app", "a"])]
     Apply(apply::Opt),
 
+    /// Apply the current theme again, e.g. after adding plugins or editing templates.
+    ///
+    /// Plugin selection and dry runs work the same as with `apply`.
+    #[structopt(aliases = &["re"])]
+    Reapply(apply::ReapplyOpt),
+
     /// Modify one or more properties of an existing theme.
     ///
     /// Currently, only some properties can be changed using this command.
@@ -53,6 +77,34 @@ enum Commands {
     #[structopt(aliases = &["ext", "e"])]
     Extract(extraction::Opt),
 
+    /// Manage the theme library.
+    ///
+    /// Saved themes can be applied by name, like `luthien apply <name>`.
+    #[structopt(aliases = &["themes", "t"])]
+    Theme(themes::Opt),
+
+    /// Print the theme which is currently applied.
+    #[structopt(aliases = &["cur"])]
+    Current(current::Opt),
+
+    /// List previously applied themes, most recent first.
+    #[structopt(aliases = &["hist"])]
+    History(history::Opt),
+
+    /// Apply a previously applied theme.
+    ///
+    /// By default, this applies the theme before the current one.
+    #[structopt(aliases = &["undo"])]
+    Rollback(history::RollbackOpt),
+
+    /// List and inspect plugins.
+    #[structopt(aliases = &["plugin", "p"])]
+    Plugins(plugins::Opt),
+
+    /// Inspect and validate the configuration file.
+    #[structopt(aliases = &["conf", "c"])]
+    Config(config::Opt),
+
     /// Generate shell completions and print to stdout
     Completions {
         #[structopt(possible_values = &clap::Shell::variants())]
@@ -76,6 +128,17 @@ impl Opt {
     }
 }
 
+/// Load the configuration and run the command with it, returning the configuration as well so
+/// that the resulting theme can be applied.
+fn run_with_config(cmd: impl Command, paths: &Paths) -> Result<(Option<Theme>, Config)> {
+    let config = paths
+        .get_config()
+        .wrap_err("Failed to load configuration")?;
+    let theme = cmd.run(paths, &config)?;
+
+    Ok((theme, config))
+}
+
 fn init_logger() {
     if std::env::var("RUST_LOG").is_err() {
         std::env::set_var("RUST_LOG", "info");
@@ -96,16 +159,10 @@ fn main() -> Result<()> {
     paths
         .ensure_initialized()
         .wrap_err("Failed to initialize config, data, and/or cache directories")?;
-    let config = paths
-        .get_config()
-        .wrap_err("Failed to load configuration")?;
 
     trace!("Running command...");
-    let res = match opt.command {
-        Commands::Apply(cmd) => cmd.run(&paths, &config)?,
-        Commands::Modify(cmd) => cmd.run(&paths, &config)?,
-        Commands::Extract(cmd) => cmd.run(&paths, &config)?,
-
+    let (res, config) = match opt.command {
+        Commands::Config(cmd) => return cmd.run(&paths),
         Commands::Completions { shell } => {
             info!("Generating completions...");
             Opt::clap().gen_completions_to(
@@ -114,8 +171,18 @@ fn main() -> Result<()> {
                 &mut std::io::stdout().lock(),
             );
 
-            None
+            return Ok(());
         }
+
+        Commands::Apply(cmd) => run_with_config(cmd, &paths)?,
+        Commands::Reapply(cmd) => run_with_config(cmd, &paths)?,
+        Commands::Modify(cmd) => run_with_config(cmd, &paths)?,
+        Commands::Extract(cmd) => run_with_config(cmd, &paths)?,
+        Commands::Theme(cmd) => run_with_config(cmd, &paths)?,
+        Commands::Current(cmd) => run_with_config(cmd, &paths)?,
+        Commands::History(cmd) => run_with_config(cmd, &paths)?,
+        Commands::Rollback(cmd) => run_with_config(cmd, &paths)?,
+        Commands::Plugins(cmd) => run_with_config(cmd, &paths)?,
     };
 
     if let Some(theme) = res {
@@ -130,8 +197,19 @@ fn main() -> Result<()> {
             .wrap_err("Failed to serialize the theme")?;
         }
 
-        if opt.apply_step {
-            apply::apply(&config, theme).wrap_err("Failed to apply the theme")?;
+        if opt.apply_step && opt.dry_run {
+            apply::dry_run(
+                &mut std::io::stdout(),
+                &paths,
+                &config,
+                &opt.selection,
+                theme,
+            )
+            .wrap_err("Failed to plan applying the theme")?;
+        } else if opt.apply_step {
+            let on_failure = opt.on_failure.unwrap_or(config.apply.on_failure);
+            apply::apply(&paths, &config, &opt.selection, on_failure, theme)
+                .wrap_err("Failed to apply the theme")?;
         }
     }
 
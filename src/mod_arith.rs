@@ -73,6 +73,32 @@ where
     pub fn length(&self) -> N {
         self.length
     }
+
+    pub fn end(&self) -> N {
+        self.space.modulo(self.start + self.length)
+    }
+
+    pub fn midpoint(&self) -> N {
+        self.space
+            .modulo(self.start + self.length / (N::one() + N::one()))
+    }
+
+    /// Distance from `n` to the nearest bound of the range, or zero if `n` is within it.
+    pub fn distance(&self, n: N) -> N {
+        if self.contains(n) {
+            return N::zero();
+        }
+
+        let n = self.space.modulo(n);
+        let after = self.space.dist_pos(self.end(), n);
+        let before = self.space.dist_pos(n, self.start);
+
+        if after < before {
+            after
+        } else {
+            before
+        }
+    }
 }
 
 #[cfg(test)]
@@ -132,4 +158,18 @@ mod tests {
         assert!(range.contains(180));
         assert!(range.contains(360));
     }
+
+    #[test]
+    fn modular_range_distance() {
+        let range = Range::new(Space::new(360), 345, 15);
+
+        assert_eq!(range.end(), 15);
+        assert_eq!(range.midpoint(), 0);
+        assert_eq!(range.distance(0), 0);
+        assert_eq!(range.distance(350), 0);
+        assert_eq!(range.distance(20), 5);
+        assert_eq!(range.distance(335), 10);
+        assert_eq!(range.distance(180), 165);
+        assert_eq!(range.distance(-25), 10);
+    }
 }
@@ -27,6 +27,7 @@ impl crate::Command for Opt {
         if self.wallpaper.is_some() {
             info!("Setting wallpaper...");
             theme.wallpaper = self.wallpaper;
+            theme.canonicalize_wallpaper()?;
         }
 
         if self.swap {
@@ -1,6 +1,8 @@
-use crate::{color, theme};
-use color_eyre::eyre::{Report, Result, WrapErr};
+use crate::plugin::Plugin;
+use crate::{color, plugins, theme};
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use log::warn;
+use palette::{Hsl, Srgb};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::{self, File};
@@ -8,12 +10,17 @@ use std::hash::{Hash, Hasher};
 use std::io;
 use std::mem;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct Paths {
     pub config: PathBuf,
     pub themes: PathBuf,
     pub cache: PathBuf,
+    /// Directory searched for plugin executables when discovery is enabled.
+    pub plugins: PathBuf,
+    /// Directory containing previously applied themes.
+    pub history: PathBuf,
 }
 
 impl Paths {
@@ -28,7 +35,7 @@ impl Paths {
 
         if !self.config.exists() {
             self.config.parent().map(fs::create_dir_all);
-            fs::write(&self.config, [])?;
+            fs::write(&self.config, Config::commented_default()?)?;
         }
 
         Ok(())
@@ -36,7 +43,12 @@ impl Paths {
 
     pub fn get_config(&self) -> Result<Config> {
         match fs::read(&self.config) {
-            Ok(raw) => toml::from_slice(&raw).wrap_err("Failed to deserialize configuration file"),
+            Ok(raw) => {
+                let config: Config =
+                    toml::from_slice(&raw).wrap_err("Failed to deserialize configuration file")?;
+                config.validate()?;
+                Ok(config)
+            }
             Err(err) => {
                 if err.kind() == io::ErrorKind::NotFound {
                     warn!("Config file not found; Using default");
@@ -48,13 +60,80 @@ impl Paths {
         }
     }
 
+    /// Read a theme from a file, or from the theme library by name.
+    ///
+    /// A relative wallpaper path is resolved relative to the theme file.
     pub fn get_theme(&self, locator: impl AsRef<Path>) -> Result<theme::Theme> {
-        let file = File::open(&locator)
-            .or_else(|_| File::open(self.themes.join(locator)))
+        let locator = locator.as_ref();
+        let (path, file) = File::open(locator)
+            .map(|file| (locator.to_path_buf(), file))
+            .or_else(
+                |err| match locator.to_str().map(|name| self.theme_path(name)) {
+                    Some(Ok(path)) => File::open(&path).map(|file| (path, file)),
+                    _ => Err(err),
+                },
+            )
             .wrap_err("Failed to read theme file")?;
         let reader = io::BufReader::new(file);
 
-        serde_json::from_reader(reader).wrap_err("Failed to deserialize theme")
+        let mut theme: theme::Theme =
+            serde_json::from_reader(reader).wrap_err("Failed to deserialize theme")?;
+        let dir = std::env::current_dir()
+            .wrap_err("Failed to get the working directory")?
+            .join(path.parent().unwrap_or_else(|| Path::new("")));
+        theme.resolve_wallpaper(&dir);
+
+        Ok(theme)
+    }
+
+    /// Path of the theme with the given name in the theme library.
+    ///
+    /// The name may be given with or without the `.json` extension.
+    pub fn theme_path(&self, name: &str) -> Result<PathBuf> {
+        let name = name.strip_suffix(".json").unwrap_or(name);
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(eyre!("Invalid theme name \"{}\"", name));
+        }
+
+        Ok(self.themes.join(format!("{}.json", name)))
+    }
+
+    /// Save the theme to the theme library.
+    ///
+    /// An existing theme with the same name is only replaced if `force` is set.
+    pub fn save_theme(&self, name: &str, theme: &theme::Theme, force: bool) -> Result<PathBuf> {
+        let path = self.theme_path(name)?;
+        if path.exists() && !force {
+            return Err(eyre!(
+                "A theme named \"{}\" already exists; use --force to replace it",
+                name
+            ));
+        }
+
+        serde_json::to_writer_pretty(
+            File::create(&path).wrap_err("Failed to create theme file")?,
+            theme,
+        )
+        .wrap_err("Failed to write theme file")?;
+        Ok(path)
+    }
+
+    /// Names of the themes in the theme library, in alphabetical order.
+    pub fn theme_names(&self) -> Result<Vec<String>> {
+        let mut names: Vec<_> = fs::read_dir(&self.themes)
+            .wrap_err("Failed to read themes directory")?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()?
+                    .strip_suffix(".json")
+                    .map(String::from)
+            })
+            .collect();
+        names.sort();
+
+        Ok(names)
     }
 }
 
@@ -67,9 +146,14 @@ impl Default for Paths {
         Self {
             config: config_root.join("config.toml"),
             themes: config_root.join("themes"),
+            plugins: config_root.join("plugins"),
             cache: dirs::cache_dir()
                 .expect("Couldn't find cache directory")
                 .join("luthien"),
+            history: dirs::data_local_dir()
+                .expect("Couldn't find local data directory")
+                .join("luthien")
+                .join("history"),
         }
     }
 }
@@ -79,6 +163,17 @@ pub struct RegionConfig {
     hue: (f32, f32),
     saturation: (f32, f32),
     lightness: (f32, f32),
+    #[serde(default)]
+    falloff: FalloffConfig,
+}
+
+/// Width of the soft edge outside of each axis of a [`RegionConfig`].
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FalloffConfig {
+    hue: f32,
+    saturation: f32,
+    lightness: f32,
 }
 
 impl PartialEq for RegionConfig {
@@ -106,6 +201,11 @@ impl From<RegionConfig> for color::Region<f32> {
             config.saturation.0..=config.saturation.1,
             config.lightness.0..=config.lightness.1,
         )
+        .with_falloff(color::Falloff {
+            hue: config.falloff.hue,
+            saturation: config.falloff.saturation,
+            lightness: config.falloff.lightness,
+        })
     }
 }
 
@@ -118,41 +218,49 @@ impl Default for theme::Palette<RegionConfig> {
                 hue: (0.0, 360.0),
                 saturation: (0.0, 1.0),
                 lightness: (0.0, PRIMARY_LIGHTNESS.0),
+                falloff: FalloffConfig::default(),
             },
             red: RegionConfig {
                 hue: (345.0, 15.0),
                 saturation: PRIMARY_SAT,
                 lightness: PRIMARY_LIGHTNESS,
+                falloff: FalloffConfig::default(),
             },
             green: RegionConfig {
                 hue: (90.0, 150.0),
                 saturation: PRIMARY_SAT,
                 lightness: PRIMARY_LIGHTNESS,
+                falloff: FalloffConfig::default(),
             },
             yellow: RegionConfig {
                 hue: (45.0, 75.0),
                 saturation: PRIMARY_SAT,
                 lightness: PRIMARY_LIGHTNESS,
+                falloff: FalloffConfig::default(),
             },
             blue: RegionConfig {
                 hue: (210.0, 255.0),
                 saturation: PRIMARY_SAT,
                 lightness: PRIMARY_LIGHTNESS,
+                falloff: FalloffConfig::default(),
             },
             purple: RegionConfig {
                 hue: (270.0, 300.0),
                 saturation: PRIMARY_SAT,
                 lightness: PRIMARY_LIGHTNESS,
+                falloff: FalloffConfig::default(),
             },
             cyan: RegionConfig {
                 hue: (165.0, 195.0),
                 saturation: PRIMARY_SAT,
                 lightness: PRIMARY_LIGHTNESS,
+                falloff: FalloffConfig::default(),
             },
             white: RegionConfig {
                 hue: (0.0, 360.0),
                 saturation: (0.0, 0.5),
                 lightness: (PRIMARY_LIGHTNESS.1, 1.0),
+                falloff: FalloffConfig::default(),
             },
         }
     }
@@ -166,9 +274,46 @@ pub struct PluginConfigRaw {
     #[serde(default)]
     pub args: Vec<String>,
     #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub after: Vec<String>,
+    #[serde(default)]
     pub env: HashMap<String, String>,
     #[serde(default)]
+    pub overrides: ThemeOverrides,
+    #[serde(default)]
     pub options: serde_json::Value,
+    pub timeout: Option<f64>,
+    pub protocol: Option<ProtocolConfig>,
+    #[serde(default)]
+    pub kind: PluginKind,
+}
+
+/// What a plugin does with the theme.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginKind {
+    /// Applies the theme somewhere, e.g. to a program's configuration.
+    #[default]
+    Sink,
+    /// Writes a modified theme to stdout, which is passed on to the following plugins.
+    Transform,
+}
+
+/// How to determine which plugin protocol version to use with a plugin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProtocolConfig {
+    /// The plugin only supports this version.
+    Version(u32),
+    Keyword(ProtocolKeyword),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProtocolKeyword {
+    /// Ask the plugin which versions it supports by running it with `--luthien-protocol`.
+    Probe,
 }
 
 impl From<PluginConfigRaw> for PluginConfig {
@@ -187,7 +332,7 @@ impl From<PluginConfigRaw> for PluginConfig {
                     dirs::config_dir()
                         .map(|p| p.join("luthien"))
                         .or_else(dirs::home_dir)
-                        .unwrap_or_else(PathBuf::new)
+                        .unwrap_or_default()
                         .join(raw.executable)
                 } else {
                     raw.executable
@@ -196,49 +341,576 @@ impl From<PluginConfigRaw> for PluginConfig {
 
             name: raw.name,
             args: raw.args,
+            tags: raw.tags,
+            after: raw.after,
             env: raw.env,
+            overrides: raw.overrides,
             options: raw.options,
+            timeout: raw.timeout,
+            protocol: raw.protocol,
+            kind: raw.kind,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(from = "PluginConfigRaw")]
 pub struct PluginConfig {
     pub executable: PathBuf,
     pub name: Option<String>,
+    /// Transform plugins are run in order before all other plugins.
+    pub kind: PluginKind,
 
+    // Tables must come last for the config to be serializable as TOML.
     pub args: Vec<String>,
+    /// Labels for selecting groups of plugins with `--tag`.
+    pub tags: Vec<String>,
+    /// Names of plugins which must finish before this plugin runs.
+    pub after: Vec<String>,
+    /// Seconds after which the plugin is terminated. Overrides [`ApplyConfig::timeout`].
+    pub timeout: Option<f64>,
+    /// Protocol versions supported by the plugin. If unset, the latest version is used.
+    pub protocol: Option<ProtocolConfig>,
     pub env: HashMap<String, String>,
+    /// Changes made to the theme before it's given to this plugin.
+    #[serde(skip_serializing_if = "ThemeOverrides::is_empty")]
+    pub overrides: ThemeOverrides,
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
     pub options: serde_json::Value,
 }
 
+impl PluginConfig {
+    /// The plugin's timeout, falling back to the global default.
+    pub fn timeout(&self, apply: &ApplyConfig) -> Option<Duration> {
+        self.timeout
+            .or(apply.timeout)
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+    }
+}
+
+/// Check that a timeout is a positive number of seconds which fits in a [`Duration`].
+pub fn check_timeout(secs: f64) -> Result<()> {
+    match Duration::try_from_secs_f64(secs) {
+        Ok(duration) if !duration.is_zero() => Ok(()),
+        _ => Err(eyre!(
+            "Timeout must be a positive number of seconds, not {}",
+            secs
+        )),
+    }
+}
+
+/// Changes made to the theme given to a single plugin, in the order of the fields.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeOverrides {
+    /// Swap the foreground and background colors.
+    pub swap: bool,
+    /// Replacement colors as hex codes, keyed by color name.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub set: HashMap<String, String>,
+    /// Amounts added to the HSL lightness of colors, keyed by color name. Negative values darken.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub lighten: HashMap<String, f32>,
+}
+
+impl ThemeOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Check that every overridden color exists, and every replacement color is a valid hex code.
+    ///
+    /// Accents are only checked when the overrides are applied, since themes have any number of them.
+    pub fn validate(&self) -> Result<()> {
+        for name in self.set.keys().chain(self.lighten.keys()) {
+            if !is_color_name(name) {
+                return Err(eyre!(
+                    "There is no color named \"{}\"; use foreground, background, a palette color, or accent_N",
+                    name
+                ));
+            }
+        }
+        for (name, hex) in &self.set {
+            if theme::parse_hex(hex).is_none() {
+                return Err(eyre!("Invalid hex code for {}: \"{}\"", name, hex));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply the overrides to the theme.
+    pub fn apply(&self, theme: &mut theme::Theme) -> Result<()> {
+        fn color<'a>(colors: &'a mut theme::Colors, name: &str) -> Result<&'a mut Srgb> {
+            colors
+                .get_mut(name)
+                .ok_or_else(|| eyre!("The theme has no color named \"{}\"", name))
+        }
+        let colors = &mut theme.colors;
+
+        if self.swap {
+            mem::swap(&mut colors.foreground, &mut colors.background);
+        }
+
+        for (name, hex) in &self.set {
+            *color(colors, name)? = theme::parse_hex(hex)
+                .ok_or_else(|| eyre!("Invalid hex code for {}: \"{}\"", name, hex))?;
+        }
+
+        for (name, amount) in &self.lighten {
+            let color = color(colors, name)?;
+            let mut hsl = Hsl::from(*color);
+            hsl.lightness = (hsl.lightness + amount).clamp(0.0, 1.0);
+            *color = Srgb::from(hsl);
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a theme could have a color with this name; see [`theme::Colors::get_mut`].
+fn is_color_name(name: &str) -> bool {
+    let palette = theme::Palette::NAMES;
+    ["foreground", "background", palette.black, palette.white]
+        .iter()
+        .chain(&palette.accents())
+        .any(|&color| color == name)
+        || name
+            .strip_prefix("accent_")
+            .is_some_and(|i| i.parse::<usize>().is_ok())
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct ExtractionConfig {
     pub target: theme::Palette<RegionConfig>,
+    /// Named alternatives to the default extraction configuration.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub profiles: HashMap<String, ExtractionProfile>,
+}
+
+impl ExtractionConfig {
+    /// Get the configuration for the named profile, or the default configuration if no name is
+    /// given.
+    pub fn profile(&self, name: Option<&str>) -> Result<Self> {
+        let target = match name {
+            Some(name) => {
+                let profile = self
+                    .profiles
+                    .get(name)
+                    .ok_or_else(|| eyre!("No extraction profile named \"{}\"", name))?;
+                self.target
+                    .zip(profile.target)
+                    .map(|(default, region)| region.unwrap_or(default))
+            }
+            None => self.target,
+        };
+
+        Ok(Self {
+            target,
+            profiles: HashMap::new(),
+        })
+    }
+}
+
+/// A named alternative to the default extraction configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExtractionProfile {
+    /// Regions which replace those of the default target; the rest are the same as the default.
+    pub target: theme::Palette<Option<RegionConfig>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApplyConfig {
+    /// Maximum number of plugins to run at once. Defaults to the number of CPUs.
+    pub concurrency: Option<usize>,
+    /// Default number of seconds after which plugins are terminated.
+    pub timeout: Option<f64>,
+    /// Whether to run `luthien-*` executables found on `PATH` and in the plugins directory.
+    pub discover: bool,
+    /// Names of the plugins to run. If unset, all plugins are run.
+    pub enabled: Option<Vec<String>>,
+    /// Names of plugins which shouldn't be run.
+    pub disabled: Vec<String>,
+    /// What to do when a plugin fails.
+    pub on_failure: FailurePolicy,
+    /// Number of applied themes to remember for `luthien rollback`. Defaults to 20.
+    pub history: Option<usize>,
+}
+
+/// What to do when a plugin fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailurePolicy {
+    /// Keep running other plugins, and exit successfully.
+    Continue,
+    /// Keep running other plugins, but exit with an error.
+    #[default]
+    FailAtEnd,
+    /// Stop starting plugins, and exit with an error once running plugins finish.
+    FailFast,
+}
+
+impl std::str::FromStr for FailurePolicy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "continue" => Ok(Self::Continue),
+            "fail-at-end" => Ok(Self::FailAtEnd),
+            "fail-fast" => Ok(Self::FailFast),
+            _ => Err("Invalid failure policy"),
+        }
+    }
+}
+
+impl ApplyConfig {
+    /// Whether the plugin with the given name should be run.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.enabled
+            .as_ref()
+            .is_none_or(|enabled| enabled.iter().any(|n| n == name))
+            && !self.disabled.iter().any(|n| n == name)
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
     pub plugins: Vec<PluginConfig>,
+    /// Plugins which extract themes, run with `luthien extract plugin`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extractors: Vec<PluginConfig>,
+    pub apply: ApplyConfig,
     pub extraction: ExtractionConfig,
 }
 
+const CONFIG_HEADER: &str = "\
+# Luthien configuration file.
+#
+# Plugins are run in order whenever a theme is applied. Relative executable
+# paths are resolved relative to Luthien's configuration directory (usually
+# `~/.config/luthien`), even if this file is elsewhere, and `~` is expanded to
+# your home directory. For example:
+#
+# [[plugins]]
+# name = \"terminal\"
+# executable = \"~/.cargo/bin/luthien-terminal\"
+# args = []
+# env = {}
+# options = {}
+#
+# The rest of this file lists the default settings. Uncomment and edit them
+# to override the defaults.
+";
+
+impl Config {
+    /// Check for problems which can't be caught while deserializing.
+    pub fn validate(&self) -> Result<()> {
+        for secs in self
+            .plugins
+            .iter()
+            .chain(&self.extractors)
+            .filter_map(|pl| pl.timeout)
+            .chain(self.apply.timeout)
+        {
+            check_timeout(secs)?;
+        }
+
+        self.validate_plugins()
+    }
+
+    /// Check plugins' overrides and dependencies, including that each dependency names a plugin.
+    ///
+    /// Unlike [`Config::validate`], this doesn't check timeouts, which `config check` reports
+    /// along with their location.
+    pub fn validate_plugins(&self) -> Result<()> {
+        for pl in self.plugins.iter().chain(&self.extractors) {
+            pl.overrides
+                .validate()
+                .wrap_err_with(|| format!("Invalid theme overrides for plugin {}", pl.name()))?;
+        }
+
+        // Discovered plugins can only be checked once they're found, when the plugins are run.
+        if !self.apply.discover {
+            let names: Vec<_> = self.plugins.iter().map(Plugin::name).collect();
+            plugins::check_dependencies(&self.plugins, &names)?;
+        }
+
+        let deps = plugins::dependencies(&self.plugins);
+        if let Err(cycle) = plugins::topological_order(&deps) {
+            let names: Vec<_> = cycle
+                .iter()
+                .chain(cycle.first())
+                .map(|&i| self.plugins[i].name())
+                .collect();
+            return Err(eyre!(
+                "Plugin dependencies form a cycle: {}",
+                names.join(" -> ")
+            ));
+        }
+
+        plugins::check_transforms(&self.plugins, &deps)
+    }
+
+    /// Serialize the configuration as TOML.
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).wrap_err("Failed to serialize configuration")
+    }
+
+    /// A configuration file containing the default configuration, commented out and annotated.
+    pub fn commented_default() -> Result<String> {
+        let mut contents = String::from(CONFIG_HEADER);
+
+        for line in Self::default().to_toml()?.lines() {
+            contents.push('\n');
+            if !line.is_empty() {
+                contents.push_str("# ");
+                contents.push_str(line);
+            }
+        }
+        contents.push('\n');
+
+        Ok(contents)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Paths;
+    use super::{Config, ExtractionProfile, FailurePolicy, Paths, RegionConfig, ThemeOverrides};
+    use crate::test_util::{self, TempDir};
+    use crate::theme::{Palette, Theme};
+    use palette::{Hsl, Srgb};
     use std::path::PathBuf;
 
     #[test]
     fn get_config() {
+        let _env = test_util::lock_env();
         let paths = Paths::default();
         paths.get_config().unwrap();
     }
 
+    #[test]
+    fn commented_default() {
+        let commented = Config::commented_default().unwrap();
+        assert!(commented
+            .lines()
+            .all(|line| line.is_empty() || line.starts_with('#')));
+
+        let uncommented: String = commented[super::CONFIG_HEADER.len()..]
+            .lines()
+            .map(|line| line.trim_start_matches("# "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let config: Config = toml::from_str(&uncommented).unwrap();
+        assert_eq!(config.extraction.target, Palette::<RegionConfig>::default());
+    }
+
+    #[test]
+    fn extraction_profiles() {
+        let mut config = Config::default();
+        config.extraction.target.green.hue = (90.0, 150.0);
+        let mut red = config.extraction.target.red;
+        red.hue = (340.0, 20.0);
+        config.extraction.profiles.insert(
+            "photo".into(),
+            ExtractionProfile {
+                target: Palette {
+                    red: Some(red),
+                    ..Palette::default()
+                },
+            },
+        );
+
+        let parsed: Config = toml::from_str(&config.to_toml().unwrap()).unwrap();
+        let extraction = &parsed.extraction;
+        let photo = extraction.profile(Some("photo")).unwrap();
+
+        assert_eq!(photo.target.red.hue, (340.0, 20.0));
+        assert_eq!(photo.target.green.hue, (90.0, 150.0));
+        assert_eq!(
+            extraction.profile(None).unwrap().target.red.hue,
+            (345.0, 15.0)
+        );
+        assert!(extraction.profile(Some("illustration")).is_err());
+
+        let parsed: Config = toml::from_str(
+            "[extraction.profiles.photo.target.red]\nhue = [340.0, 20.0]\nsaturation = [0.1, 1.0]\nlightness = [0.1, 0.9]\n",
+        )
+        .unwrap();
+        let photo = parsed.extraction.profile(Some("photo")).unwrap();
+        assert_eq!(photo.target.red.hue, (340.0, 20.0));
+        assert_eq!(photo.target.blue, Config::default().extraction.target.blue);
+        assert!(toml::from_str::<Config>("[extraction.profiles.photo.profiles.dark]\n").is_err());
+    }
+
+    #[test]
+    fn extractors() {
+        let config: Config = toml::from_str(
+            "[[plugins]]\nexecutable = \"/bin/true\"\n\n[[extractors]]\nname = \"raw\"\nexecutable = \"/bin/cat\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.extractors[0].name.as_deref(), Some("raw"));
+
+        let mut config = config;
+        config.plugins[0].timeout = Some(1.0);
+        config.extractors.clear();
+        config.to_toml().unwrap();
+    }
+
+    #[test]
+    fn validate_timeouts() {
+        let config = |raw: &str| toml::from_str::<Config>(raw).unwrap().validate();
+
+        assert!(config("[apply]\ntimeout = 2.5\n").is_ok());
+        assert!(config("[apply]\ntimeout = 0.0\n").is_err());
+        assert!(config("[apply]\ntimeout = 1e300\n").is_err());
+        assert!(config("[apply]\ntimeout = inf\n").is_err());
+        assert!(config("[[plugins]]\nexecutable = \"/bin/true\"\ntimeout = -1.0\n").is_err());
+    }
+
+    #[test]
+    fn validate_dependencies() {
+        let config = |raw: &str| toml::from_str::<Config>(raw).unwrap().validate();
+
+        assert!(config(
+            "[[plugins]]\nname = \"a\"\nexecutable = \"/bin/true\"\nafter = [\"b\"]\n\
+             [[plugins]]\nname = \"b\"\nexecutable = \"/bin/true\"\n"
+        )
+        .is_ok());
+        assert!(
+            config("[[plugins]]\nname = \"a\"\nexecutable = \"/bin/true\"\nafter = [\"a\"]\n")
+                .is_err()
+        );
+        assert!(config(
+            "[[plugins]]\nname = \"a\"\nexecutable = \"/bin/true\"\nkind = \"transform\"\nafter = [\"b\"]\n\
+             [[plugins]]\nname = \"b\"\nexecutable = \"/bin/true\"\n"
+        )
+        .is_err());
+        assert!(
+            config("[[plugins]]\nname = \"a\"\nexecutable = \"/bin/true\"\nafter = [\"c\"]\n")
+                .is_err()
+        );
+        // The dependency may be a discovered plugin, which is only checked when plugins are run.
+        assert!(config(
+            "[apply]\ndiscover = true\n\n[[plugins]]\nname = \"a\"\nexecutable = \"/bin/true\"\nafter = [\"c\"]\n"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn theme_overrides() {
+        let config: Config = toml::from_str(
+            "[[plugins]]\nexecutable = \"/bin/true\"\n\
+             [plugins.overrides]\nswap = true\n\
+             set = { foreground = \"#ff0000\" }\n\
+             lighten = { background = -0.5 }\n",
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let mut theme = test_util::theme();
+        config.plugins[0].overrides.apply(&mut theme).unwrap();
+
+        // The original white foreground is swapped into the background, then darkened to grey.
+        assert_eq!(theme.colors.foreground, Srgb::new(1.0, 0.0, 0.0));
+        let background = Hsl::from(theme.colors.background);
+        assert!(
+            (background.lightness - 0.5).abs() < 1e-6,
+            "{:?}",
+            background
+        );
+
+        let overrides = |raw: &str| toml::from_str::<ThemeOverrides>(raw).unwrap();
+        assert!(overrides("set = { background = \"#12345\" }")
+            .validate()
+            .is_err());
+        assert!(overrides("set = { orange = \"#123456\" }")
+            .validate()
+            .is_err());
+        assert!(overrides("lighten = { orange = 0.1 }").validate().is_err());
+        assert!(overrides("lighten = { accent_3 = 0.1, red = 0.1 }")
+            .validate()
+            .is_ok());
+        assert!(overrides("set = { accent_9 = \"#123456\" }")
+            .apply(&mut theme)
+            .is_err());
+    }
+
+    #[test]
+    fn failure_policy() {
+        let config: Config = toml::from_str("[apply]\non_failure = \"fail-fast\"\n").unwrap();
+        assert_eq!(config.apply.on_failure, FailurePolicy::FailFast);
+        assert_eq!(Config::default().apply.on_failure, FailurePolicy::FailAtEnd);
+        assert_eq!("continue".parse(), Ok(FailurePolicy::Continue));
+    }
+
+    #[test]
+    fn theme_path() {
+        let _env = test_util::lock_env();
+        let paths = Paths::default();
+
+        assert_eq!(
+            paths.theme_path("dark").unwrap(),
+            paths.themes.join("dark.json")
+        );
+        assert_eq!(
+            paths.theme_path("dark.json").unwrap(),
+            paths.themes.join("dark.json")
+        );
+        assert!(paths.theme_path("../dark").is_err());
+        assert!(paths.theme_path(".json").is_err());
+    }
+
+    #[test]
+    fn save_theme() {
+        let _env = test_util::lock_env();
+        let root = TempDir::new("save_theme");
+        let paths = Paths {
+            themes: root.to_path_buf(),
+            ..Paths::default()
+        };
+        let theme = test_util::theme();
+        let other = Theme {
+            wallpaper: None,
+            ..test_util::theme()
+        };
+
+        let path = paths.save_theme("dark", &theme, false).unwrap();
+        assert!(paths.save_theme("dark", &other, false).is_err());
+        assert_eq!(paths.get_theme(&path).unwrap().colors, theme.colors);
+
+        paths.save_theme("dark", &other, true).unwrap();
+        assert_eq!(paths.get_theme(&path).unwrap().wallpaper, None);
+    }
+
+    #[test]
+    fn get_theme_wallpaper() {
+        let _env = test_util::lock_env();
+        let dir = TempDir::new("wallpaper");
+
+        let mut theme = serde_json::to_value(Theme {
+            wallpaper: Some(PathBuf::from("wallpaper.png")),
+            ..test_util::theme()
+        })
+        .unwrap();
+        std::fs::write(dir.join("relative.json"), theme.to_string()).unwrap();
+        theme["wallpaper"] = "/wallpaper.png".into();
+        std::fs::write(dir.join("absolute.json"), theme.to_string()).unwrap();
+
+        let paths = Paths::default();
+        let relative = paths.get_theme(dir.join("relative.json")).unwrap();
+        let absolute = paths.get_theme(dir.join("absolute.json")).unwrap();
+
+        assert_eq!(relative.wallpaper, Some(dir.join("wallpaper.png")));
+        assert_eq!(absolute.wallpaper, Some(PathBuf::from("/wallpaper.png")));
+    }
+
     #[test]
     fn get_nonexistent_theme() {
+        let _env = test_util::lock_env();
         let paths = Paths::default();
         let theme = paths.get_theme(PathBuf::from("/non/existent/path"));
         debug_assert!(theme.is_err(), "Theme was Ok:\n{:?}", theme);
@@ -1,13 +1,18 @@
-use crate::persist::PluginConfig;
-use crate::theme::Theme;
+use crate::persist::{PluginConfig, PluginKind, ProtocolConfig, ProtocolKeyword};
+use crate::theme::{self, Palette, Theme};
 use color_eyre::eyre::{eyre, Result, WrapErr};
-use log::trace;
+use log::{debug, info, log, trace, warn};
+use palette::Srgb;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::ffi::OsStr;
 use std::fs;
-use std::io;
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, ExitStatus, Stdio};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Directories {
@@ -17,18 +22,19 @@ pub struct Directories {
     data: PathBuf,
 }
 
+/// Directory which the applied theme and wallpaper are written to.
+pub fn outputs_dir() -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join("luthien").join("outputs"))
+}
+
 impl Directories {
-    fn new(name: &str) -> Option<Self> {
+    pub fn new(name: &str) -> Option<Self> {
         Some(Self {
             config: dirs::config_dir()?
                 .join("luthien")
                 .join("plugins")
                 .join(name),
-            output: dirs::data_local_dir()?
-                .join("luthien")
-                .join("outputs")
-                .join("plugins")
-                .join(name),
+            output: outputs_dir()?.join("plugins").join(name),
             cache: dirs::cache_dir()?
                 .join("luthien")
                 .join("plugins")
@@ -37,7 +43,7 @@ impl Directories {
         })
     }
 
-    fn ensure_initialized(&self) -> Result<()> {
+    pub fn ensure_initialized(&self) -> Result<()> {
         fn create_dir(path: &Path) -> io::Result<()> {
             match fs::create_dir_all(path) {
                 Ok(_) => Ok(()),
@@ -60,6 +66,7 @@ impl Directories {
 /// Data provided to the plugin process through its stdin
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PluginInput {
+    protocol_version: u32,
     pipe: Option<PathBuf>,
     directories: Directories,
 
@@ -68,61 +75,716 @@ pub struct PluginInput {
     theme: Theme,
 }
 
+/// Standard environment variables describing a plugin.
+pub fn plugin_env(name: &str, directories: &Directories) -> Vec<(String, String)> {
+    vec![
+        ("LUTHIEN_PLUGIN_NAME".into(), name.into()),
+        (
+            "LUTHIEN_OUTPUT_DIR".into(),
+            directories.output.display().to_string(),
+        ),
+        ("LUTHIEN_VERSION".into(), env!("CARGO_PKG_VERSION").into()),
+    ]
+}
+
+/// Version of the plugin protocol spoken by this version of Luthien.
+///
+/// Version 0 is the original protocol, whose input has no `protocol_version` field.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest protocol version which Luthien can still adapt its input to.
+const MIN_PROTOCOL_VERSION: u32 = 0;
+/// Argument passed to plugins to ask which protocol versions they support.
+const PROBE_FLAG: &str = "--luthien-protocol";
+/// How long a plugin has to answer a protocol probe.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Protocol versions a plugin declares support for in response to [`PROBE_FLAG`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+struct ProtocolDeclaration {
+    protocol_versions: Vec<u32>,
+}
+
+impl PluginInput {
+    /// Standard environment variables describing the plugin and its theme.
+    ///
+    /// These let simple plugins, like shell scripts, use the theme without parsing their input.
+    fn env(&self, theme_file: Option<&Path>) -> Vec<(String, String)> {
+        let mut env = plugin_env(&self.name, &self.directories);
+        if let Some(path) = theme_file {
+            env.push(("LUTHIEN_THEME_FILE".into(), path.display().to_string()));
+        }
+
+        let colors = &self.theme.colors;
+        let palette = colors.palette.zip(Palette::NAMES);
+        let mut named: Vec<(String, Srgb)> = vec![
+            ("foreground".into(), colors.foreground),
+            ("background".into(), colors.background),
+        ];
+        named.extend(
+            palette
+                .accents()
+                .iter()
+                .chain([palette.black, palette.white].iter())
+                .map(|(color, name)| (name.to_string(), *color)),
+        );
+        named.extend(
+            colors
+                .accents
+                .iter()
+                .enumerate()
+                .map(|(i, color)| (format!("accent_{}", i), *color)),
+        );
+
+        env.extend(named.into_iter().map(|(name, color)| {
+            (
+                format!("LUTHIEN_COLOR_{}", name.to_uppercase()),
+                theme::hex(color),
+            )
+        }));
+        env
+    }
+
+    /// Serialize the input in the format expected by its protocol version.
+    pub fn to_json(&self) -> Result<serde_json::Value> {
+        let mut value = serde_json::to_value(self).wrap_err("Failed to serialize plugin input")?;
+
+        if self.protocol_version == 0 {
+            if let Some(obj) = value.as_object_mut() {
+                obj.remove("protocol_version");
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+/// Choose the newest protocol version supported by both Luthien and a plugin.
+fn negotiate_protocol(name: &str, supported: &[u32]) -> Result<u32> {
+    if let Some(version) = supported
+        .iter()
+        .copied()
+        .filter(|v| (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(v))
+        .max()
+    {
+        return Ok(version);
+    }
+
+    match supported.iter().min() {
+        Some(oldest) if *oldest > PROTOCOL_VERSION => Err(eyre!(
+            "Plugin {} requires protocol version {} or newer, but this version of Luthien only supports up to {}. Try updating Luthien.",
+            name,
+            oldest,
+            PROTOCOL_VERSION
+        )),
+        Some(_) => Err(eyre!(
+            "Plugin {} only supports protocol versions {:?}, which this version of Luthien no longer supports. Try updating the plugin.",
+            name,
+            supported
+        )),
+        None => Err(eyre!("Plugin {} declared no supported protocol versions", name)),
+    }
+}
+
+/// Whether a plugin reported that it succeeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseStatus {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<MessageLevel> for log::Level {
+    fn from(level: MessageLevel) -> Self {
+        match level {
+            MessageLevel::Error => Self::Error,
+            MessageLevel::Warn => Self::Warn,
+            MessageLevel::Info => Self::Info,
+            MessageLevel::Debug => Self::Debug,
+            MessageLevel::Trace => Self::Trace,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Message {
+    pub level: MessageLevel,
+    pub text: String,
+}
+
+/// Results reported by a plugin as a line of JSON on its stdout.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Response {
+    pub protocol_version: u32,
+    pub status: ResponseStatus,
+    #[serde(default)]
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+impl Response {
+    /// Parse a line of plugin output as a response.
+    ///
+    /// Returns [`None`] if the line isn't meant to be a response, i.e. it isn't a JSON object with a
+    /// `protocol_version` field.
+    fn parse(line: &str) -> Option<Result<Self>> {
+        let value: serde_json::Value = serde_json::from_str(line).ok()?;
+        let version = value.as_object()?.get("protocol_version")?.clone();
+
+        let supported = MIN_PROTOCOL_VERSION as u64..=PROTOCOL_VERSION as u64;
+        Some(match version.as_u64() {
+            Some(version) if supported.contains(&version) => {
+                serde_json::from_value(value).wrap_err("Malformed plugin response")
+            }
+            _ => Err(eyre!(
+                "Unsupported plugin response protocol version {} (expected {} to {})",
+                version,
+                MIN_PROTOCOL_VERSION,
+                PROTOCOL_VERSION
+            )),
+        })
+    }
+
+    /// Log the response's messages and warnings on behalf of a plugin.
+    fn log(&self, name: &str) {
+        for message in self.messages.iter() {
+            log!(message.level.into(), "[{}] {}", name, message.text);
+        }
+        for warning in self.warnings.iter() {
+            warn!("[{}] {}", name, warning);
+        }
+        for file in self.files.iter() {
+            debug!("[{}] Wrote {}", name, file.display());
+        }
+    }
+}
+
+/// How long a plugin has to exit after being sent SIGTERM before it is killed.
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+/// How often to check whether a plugin with a timeout has exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long to keep reading a plugin's output after it exits.
+///
+/// Background processes started by a plugin may inherit its stdout and stderr, so they can stay
+/// open indefinitely.
+const OUTPUT_GRACE_PERIOD: Duration = Duration::from_millis(500);
+/// Number of lines from the end of a plugin's stderr to keep for error reporting.
+const STDERR_TAIL_LINES: usize = 10;
+
+/// How a plugin process finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// The plugin exited on its own.
+    Status(ExitStatus),
+    /// The plugin didn't exit within its timeout and was terminated.
+    TimedOut(Duration),
+}
+
+/// The result of a plugin's execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub exit: Exit,
+    /// Everything the plugin wrote to stdout.
+    pub stdout: String,
+    /// The last response the plugin wrote to stdout, if any.
+    pub response: Option<Response>,
+    /// The last lines the plugin wrote to stderr.
+    pub stderr_tail: Vec<String>,
+}
+
 pub trait Plugin {
-    fn run<P: AsRef<OsStr>>(&self, theme: Theme, stdio_pipe: Option<P>) -> Result<ExitStatus>;
+    fn run<P: AsRef<OsStr>>(
+        &self,
+        theme: Theme,
+        stdio_pipe: Option<P>,
+        timeout: Option<Duration>,
+    ) -> Result<Output>;
     fn name(&self) -> String;
 }
 
+impl Output {
+    /// Whether the plugin exited successfully and didn't report a failure.
+    pub fn success(&self) -> bool {
+        let reported = self.response.as_ref().map(|res| res.status);
+
+        matches!(self.exit, Exit::Status(status) if status.success())
+            && reported != Some(ResponseStatus::Failure)
+    }
+
+    /// The theme written to stdout by a transform or extractor plugin.
+    pub fn theme(&self) -> Result<Theme> {
+        serde_json::from_str(&self.stdout).wrap_err("Plugin wrote an invalid theme")
+    }
+}
+
 impl Plugin for PluginConfig {
-    fn run<P: AsRef<OsStr>>(&self, theme: Theme, stdin_pipe: Option<P>) -> Result<ExitStatus> {
+    fn run<P: AsRef<OsStr>>(
+        &self,
+        theme: Theme,
+        stdin_pipe: Option<P>,
+        timeout: Option<Duration>,
+    ) -> Result<Output> {
+        let protocol_version = self.protocol_version(true)?;
+        trace!("Using protocol version {}.", protocol_version);
+
         trace!("Preparing plugin input.");
-        let input = {
-            let name = self.name();
-            let directories = Directories::new(&name)
-                .ok_or_else(|| eyre!("Failed to find plugin directories"))?;
-            directories
-                .ensure_initialized()
-                .wrap_err("Failed to initialize plugin directories")?;
-
-            PluginInput {
-                pipe: stdin_pipe.as_ref().map(PathBuf::from),
-                options: self.options.clone(),
-                directories,
-                name,
-                theme,
+        let input = self.input(theme, stdin_pipe, protocol_version)?;
+        input
+            .directories
+            .ensure_initialized()
+            .wrap_err("Failed to initialize plugin directories")?;
+
+        let env = self.standard_env(&input);
+
+        self.execute(
+            &[],
+            &env,
+            &input.to_json()?,
+            timeout,
+            self.kind != PluginKind::Transform,
+        )
+    }
+
+    fn name(&self) -> String {
+        self.name.as_ref().cloned().unwrap_or_else(|| {
+            self.executable
+                .file_stem()
+                .or_else(|| self.executable.file_stem())
+                .unwrap_or_else(|| self.executable.as_ref())
+                .to_string_lossy()
+                .trim_start_matches("luthien-")
+                .into()
+        })
+    }
+}
+
+impl PluginConfig {
+    /// The protocol version to use with the plugin.
+    ///
+    /// If the plugin is configured to be probed but `probe` is false, the latest version is
+    /// assumed instead.
+    pub fn protocol_version(&self, probe: bool) -> Result<u32> {
+        match self.protocol {
+            Some(ProtocolConfig::Version(version)) => negotiate_protocol(&self.name(), &[version]),
+            Some(ProtocolConfig::Keyword(ProtocolKeyword::Probe)) if probe => {
+                negotiate_protocol(&self.name(), &self.probe_protocol()?)
             }
+            _ => Ok(PROTOCOL_VERSION),
+        }
+    }
+
+    /// Standard environment variables given to the plugin along with its input.
+    ///
+    /// Variables set in [`PluginConfig::env`] take precedence over these.
+    pub fn standard_env(&self, input: &PluginInput) -> Vec<(String, String)> {
+        // Transforms run before the theme file is written, so it would be out of date.
+        let theme_file = match self.kind {
+            PluginKind::Sink => outputs_dir().map(|dir| dir.join("theme.json")),
+            PluginKind::Transform => None,
         };
+        input.env(theme_file.as_deref())
+    }
+
+    /// Prepare the plugin's input, applying its theme overrides, without creating its directories.
+    pub fn input<P: AsRef<OsStr>>(
+        &self,
+        mut theme: Theme,
+        stdin_pipe: Option<P>,
+        protocol_version: u32,
+    ) -> Result<PluginInput> {
+        let name = self.name();
+        let directories =
+            Directories::new(&name).ok_or_else(|| eyre!("Failed to find plugin directories"))?;
+        self.overrides
+            .apply(&mut theme)
+            .wrap_err("Failed to apply theme overrides")?;
+
+        Ok(PluginInput {
+            protocol_version,
+            pipe: stdin_pipe.as_ref().map(PathBuf::from),
+            options: self.options.clone(),
+            directories,
+            name,
+            theme,
+        })
+    }
 
+    /// Run the plugin's executable with the given input, capturing its output.
+    ///
+    /// If `parse_stdout` is false, the plugin's stdout is data rather than messages, so it's
+    /// neither logged nor parsed as a [`Response`].
+    pub fn execute(
+        &self,
+        extra_args: &[&str],
+        extra_env: &[(String, String)],
+        input: &serde_json::Value,
+        timeout: Option<Duration>,
+        parse_stdout: bool,
+    ) -> Result<Output> {
         trace!("Spawning plugin process: {:?}", self.executable);
         let mut child = Command::new(&self.executable)
             .args(&self.args)
+            .args(extra_args)
+            .envs(extra_env.iter().cloned())
             .envs(&self.env)
             .stdin(Stdio::piped())
-            .stdout(Stdio::null()) // NOTE: In the future this will be piped to allow plugins to provide more data when they error
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
             .spawn()?;
 
+        trace!("Capturing plugin output.");
+        let (done_tx, done_rx) = mpsc::channel();
+        let stdout = Arc::new(Mutex::new(String::new()));
+        let stderr_tail = Arc::new(Mutex::new(VecDeque::new()));
+        let response = Arc::new(Mutex::new(None));
+        if parse_stdout {
+            let name = self.name();
+            let stdout = stdout.clone();
+            let response = response.clone();
+            read_lines(child.stdout.take(), done_tx.clone(), move |line| {
+                match Response::parse(&line) {
+                    Some(Ok(res)) => {
+                        res.log(&name);
+                        *response.lock().unwrap() = Some(res);
+                    }
+                    Some(Err(e)) => {
+                        warn!("[{}] {:#}", name, e);
+                        info!("[{}] {}", name, line);
+                    }
+                    None => info!("[{}] {}", name, line),
+                }
+
+                let mut stdout = stdout.lock().unwrap();
+                stdout.push_str(&line);
+                stdout.push('\n');
+            });
+        } else {
+            let stdout = stdout.clone();
+            read_lines(child.stdout.take(), done_tx.clone(), move |line| {
+                let mut stdout = stdout.lock().unwrap();
+                stdout.push_str(&line);
+                stdout.push('\n');
+            });
+        }
+        {
+            let name = self.name();
+            let stderr_tail = stderr_tail.clone();
+            read_lines(child.stderr.take(), done_tx, move |line| {
+                info!("[{}] {}", name, line);
+
+                let mut tail = stderr_tail.lock().unwrap();
+                if tail.len() == STDERR_TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+            });
+        }
+
         trace!("Writing plugin input.");
-        serde_json::to_writer(
-            child
-                .stdin
-                .as_mut()
-                .ok_or_else(|| eyre!("Failed to get stdin of plugin process"))?,
-            &input,
-        )?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| eyre!("Failed to get stdin of plugin process"))?;
+        write_input(self.name(), stdin, serde_json::to_vec(input)?);
 
         trace!("Waiting for plugin to finish executing");
-        Ok(child.wait()?)
-    }
+        let exit = match timeout {
+            Some(timeout) => match wait_timeout(&mut child, timeout)? {
+                Some(status) => Exit::Status(status),
+                None => {
+                    warn!("Plugin {} timed out; terminating it", self.name());
+                    terminate(&mut child)?;
+                    Exit::TimedOut(timeout)
+                }
+            },
+            None => Exit::Status(child.wait()?),
+        };
 
-    fn name(&self) -> String {
-        self.name.as_ref().cloned().unwrap_or_else(|| {
-            self.executable
-                .file_stem()
-                .or_else(|| self.executable.file_stem())
-                .unwrap_or_else(|| self.executable.as_ref())
-                .to_string_lossy()
-                .trim_start_matches("luthien-")
-                .into()
+        trace!("Waiting for plugin output to close");
+        for _ in 0..2 {
+            if done_rx.recv_timeout(OUTPUT_GRACE_PERIOD).is_err() {
+                debug!("Plugin {} left its output open after exiting", self.name());
+                break;
+            }
+        }
+
+        let stdout = stdout.lock().unwrap().clone();
+        let response = response.lock().unwrap().take();
+        let stderr_tail = stderr_tail.lock().unwrap().iter().cloned().collect();
+        Ok(Output {
+            exit,
+            stdout,
+            response,
+            stderr_tail,
         })
     }
+
+    /// Ask the plugin which protocol versions it supports.
+    fn probe_protocol(&self) -> Result<Vec<u32>> {
+        trace!("Probing protocol versions of plugin {}", self.name());
+        let mut child = Command::new(&self.executable)
+            .arg(PROBE_FLAG)
+            .envs(&self.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .wrap_err("Failed to spawn plugin to probe its protocol version")?;
+
+        let status = match wait_timeout(&mut child, PROBE_TIMEOUT)? {
+            Some(status) => status,
+            None => {
+                terminate(&mut child)?;
+                return Err(eyre!("Plugin didn't answer the protocol probe in time"));
+            }
+        };
+        if !status.success() {
+            return Err(eyre!(
+                "Plugin doesn't support protocol probing ({}); set its protocol version in the config instead",
+                status
+            ));
+        }
+
+        let declaration: ProtocolDeclaration = serde_json::from_reader(
+            child
+                .stdout
+                .take()
+                .ok_or_else(|| eyre!("Failed to get stdout of plugin process"))?,
+        )
+        .wrap_err("Plugin's protocol declaration was malformed")?;
+
+        Ok(declaration.protocol_versions)
+    }
+}
+
+/// Call a function on each line of a pipe in a background thread, notifying `done` when the pipe
+/// closes.
+fn read_lines<R, F>(pipe: Option<R>, done: mpsc::Sender<()>, mut on_line: F)
+where
+    R: Read + Send + 'static,
+    F: FnMut(String) + Send + 'static,
+{
+    thread::spawn(move || {
+        for line in pipe.into_iter().flat_map(|p| BufReader::new(p).lines()) {
+            match line {
+                Ok(line) => on_line(line),
+                Err(_) => break,
+            }
+        }
+        let _ = done.send(());
+    });
+}
+
+/// Write a plugin's input on its own thread, so that the timeout still applies to a plugin which
+/// stops reading it.
+fn write_input(name: String, mut pipe: ChildStdin, input: Vec<u8>) {
+    thread::spawn(move || match pipe.write_all(&input) {
+        Ok(()) => {}
+        // The plugin exited without reading its input, so its exit status decides the result.
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {
+            debug!("Plugin {} didn't read its input", name)
+        }
+        Err(err) => warn!("Failed to write the input of plugin {}: {}", name, err),
+    });
+}
+
+/// Wait for a child to exit, returning [`None`] if it doesn't exit within the timeout.
+fn wait_timeout(child: &mut Child, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if start.elapsed() >= timeout {
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Ask a child to exit with SIGTERM, killing it if it doesn't exit within [`KILL_GRACE_PERIOD`].
+fn terminate(child: &mut Child) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        // SAFETY: `kill` has no memory safety requirements, and the child hasn't been reaped
+        // yet so its PID can't have been reused.
+        if unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) } == 0
+            && wait_timeout(child, KILL_GRACE_PERIOD)?.is_some()
+        {
+            return Ok(());
+        }
+    }
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::theme::Colors;
+
+    #[test]
+    fn negotiate_protocol() {
+        assert_eq!(super::negotiate_protocol("test", &[0, 1]).unwrap(), 1);
+        assert_eq!(super::negotiate_protocol("test", &[0]).unwrap(), 0);
+        assert_eq!(
+            super::negotiate_protocol("test", &[PROTOCOL_VERSION, PROTOCOL_VERSION + 1]).unwrap(),
+            PROTOCOL_VERSION
+        );
+
+        let err = super::negotiate_protocol("test", &[PROTOCOL_VERSION + 1]).unwrap_err();
+        assert!(err.to_string().contains("Try updating Luthien"));
+        assert!(super::negotiate_protocol("test", &[]).is_err());
+    }
+
+    #[test]
+    fn legacy_input() {
+        let _env = test_util::lock_env();
+        let input = PluginInput {
+            protocol_version: 0,
+            pipe: None,
+            directories: Directories::new("test").unwrap(),
+            name: "test".into(),
+            options: serde_json::Value::Null,
+            theme: test_util::theme(),
+        };
+
+        let json = input.to_json().unwrap();
+        assert!(json.get("protocol_version").is_none());
+        assert!(json.get("theme").is_some());
+    }
+
+    #[test]
+    fn parse_response() {
+        assert!(Response::parse("Compiled 3 stylesheets.").is_none());
+        assert!(Response::parse("{\"status\": \"success\"}").is_none());
+        assert!(
+            Response::parse("{\"protocol_version\": 2, \"status\": \"success\"}")
+                .unwrap()
+                .is_err()
+        );
+        assert!(
+            Response::parse("{\"protocol_version\": 0, \"status\": \"success\"}")
+                .unwrap()
+                .is_ok()
+        );
+        assert!(
+            Response::parse("{\"protocol_version\": 1, \"status\": \"maybe\"}")
+                .unwrap()
+                .is_err()
+        );
+
+        let response = Response::parse(
+            r#"{"protocol_version": 1, "status": "failure", "messages": [{"level": "error", "text": "No templates found"}]}"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(response.status, ResponseStatus::Failure);
+        assert_eq!(response.messages[0].level, MessageLevel::Error);
+        assert!(response.files.is_empty());
+    }
+
+    #[test]
+    fn input_env() {
+        let _env = test_util::lock_env();
+        let palette = Palette {
+            red: Srgb::new(1.0, 0.0, 0.0),
+            ..Default::default()
+        };
+        let input = PluginInput {
+            protocol_version: PROTOCOL_VERSION,
+            pipe: None,
+            directories: Directories::new("test").unwrap(),
+            name: "test".into(),
+            options: serde_json::Value::Null,
+            theme: Theme {
+                wallpaper: None,
+                colors: Colors {
+                    palette,
+                    accents: vec![Srgb::new(0.0, 0.0, 1.0)],
+                    foreground: Srgb::new(1.0, 1.0, 1.0),
+                    background: Default::default(),
+                },
+            },
+        };
+
+        let env: std::collections::HashMap<_, _> = input
+            .env(Some(Path::new("/theme.json")))
+            .into_iter()
+            .collect();
+        let var = |name: &str| env.get(name).map(String::as_str);
+        assert_eq!(var("LUTHIEN_PLUGIN_NAME"), Some("test"));
+        assert_eq!(var("LUTHIEN_THEME_FILE"), Some("/theme.json"));
+        assert_eq!(var("LUTHIEN_COLOR_RED"), Some("#ff0000"));
+        assert_eq!(var("LUTHIEN_COLOR_WHITE"), Some("#000000"));
+        assert_eq!(var("LUTHIEN_COLOR_ACCENT_0"), Some("#0000ff"));
+        assert_eq!(var("LUTHIEN_COLOR_FOREGROUND"), Some("#ffffff"));
+        assert_eq!(env.len(), 15);
+    }
+
+    #[test]
+    fn transform_output() {
+        let theme = test_util::theme();
+        let mut output = Output {
+            exit: Exit::TimedOut(Duration::from_secs(1)),
+            stdout: serde_json::to_string_pretty(&theme).unwrap(),
+            response: None,
+            stderr_tail: Vec::new(),
+        };
+        assert_eq!(output.theme().unwrap(), theme);
+
+        output.stdout = "Not a theme\n".into();
+        assert!(output.theme().is_err());
+    }
+
+    #[test]
+    fn wait_timeout() {
+        let mut child = Command::new("sh").args(["-c", "exit 3"]).spawn().unwrap();
+        let status = super::wait_timeout(&mut child, Duration::from_secs(5))
+            .unwrap()
+            .expect("Child should exit before the timeout");
+        assert_eq!(status.code(), Some(3));
+
+        let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+        assert_eq!(
+            super::wait_timeout(&mut child, Duration::from_millis(50)).unwrap(),
+            None
+        );
+
+        let start = Instant::now();
+        terminate(&mut child).unwrap();
+        assert!(child.try_wait().unwrap().is_some());
+        assert!(start.elapsed() < KILL_GRACE_PERIOD);
+    }
+
+    #[test]
+    fn unread_input_times_out() {
+        let plugin: PluginConfig =
+            toml::from_str("executable = \"/bin/sh\"\nargs = [\"-c\", \"sleep 5\"]\n").unwrap();
+        // Larger than the pipe's buffer, so writing it blocks until the plugin is killed.
+        let input = serde_json::Value::String("x".repeat(1 << 20));
+
+        let start = Instant::now();
+        let output = plugin
+            .execute(&[], &[], &input, Some(Duration::from_millis(100)), false)
+            .unwrap();
+        assert!(matches!(output.exit, Exit::TimedOut(_)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
 }
@@ -0,0 +1,63 @@
+use super::{resolve, Source};
+use crate::persist::{Config, Paths};
+use crate::plugin::Plugin;
+use color_eyre::eyre::Result;
+use log::{info, warn};
+
+pub fn list(paths: &Paths, config: &Config) -> Result<()> {
+    let plugins = resolve(paths, config);
+
+    for name in config
+        .apply
+        .enabled
+        .iter()
+        .flatten()
+        .chain(config.apply.disabled.iter())
+    {
+        if !plugins.iter().any(|pl| &pl.config.name() == name) {
+            warn!("No plugin named {} is configured or discovered", name);
+        }
+    }
+
+    if plugins.is_empty() {
+        info!("No plugins are configured or discovered");
+        return Ok(());
+    }
+
+    let rows: Vec<_> = plugins
+        .iter()
+        .map(|pl| {
+            [
+                pl.config.name(),
+                if pl.enabled { "enabled" } else { "disabled" }.to_string(),
+                match &pl.source {
+                    Source::Config => "config".to_string(),
+                    Source::Discovered(dir) => format!("discovered in {}", dir.display()),
+                },
+                pl.config.executable.display().to_string(),
+            ]
+        })
+        .collect();
+
+    let mut widths = [0; 3];
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    for [name, status, source, executable] in rows {
+        println!(
+            "{:name_width$}  {:status_width$}  {:source_width$}  {}",
+            name,
+            status,
+            source,
+            executable,
+            name_width = widths[0],
+            status_width = widths[1],
+            source_width = widths[2],
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,216 @@
+mod list;
+mod order;
+
+pub use order::{check_dependencies, check_transforms, dependencies, topological_order};
+
+use crate::persist::{Config, Paths, PluginConfig};
+use crate::plugin::Plugin;
+use crate::theme::Theme;
+use color_eyre::eyre::Result;
+use log::{debug, trace};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+/// Prefix of executables which are discovered as plugins.
+const EXECUTABLE_PREFIX: &str = "luthien-";
+
+#[derive(Debug, PartialEq, Clone, StructOpt)]
+pub enum Opt {
+    /// List configured and discovered plugins.
+    #[structopt(aliases = &["ls"])]
+    List,
+}
+
+impl crate::Command for Opt {
+    fn run(self, paths: &Paths, config: &Config) -> Result<Option<Theme>> {
+        match self {
+            Self::List => list::list(paths, config)?,
+        }
+
+        Ok(None)
+    }
+}
+
+/// Where a plugin came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// Listed in the configuration file.
+    Config,
+    /// Found in the given directory.
+    Discovered(PathBuf),
+}
+
+/// A plugin which Luthien knows about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved {
+    pub config: PluginConfig,
+    pub source: Source,
+    pub enabled: bool,
+}
+
+/// All configured plugins, followed by discovered plugins if discovery is enabled.
+///
+/// Discovered plugins are shadowed by configured plugins with the same name, and by plugins with
+/// the same name found earlier in the search path.
+pub fn resolve(paths: &Paths, config: &Config) -> Vec<Resolved> {
+    let mut names: HashSet<_> = config.plugins.iter().map(Plugin::name).collect();
+    let mut plugins: Vec<_> = config
+        .plugins
+        .iter()
+        .map(|pl| Resolved {
+            enabled: config.apply.is_enabled(&pl.name()),
+            config: pl.clone(),
+            source: Source::Config,
+        })
+        .collect();
+
+    if config.apply.discover {
+        for (dir, executable) in discover(&search_path(paths)) {
+            let plugin = discovered_config(executable);
+            let name = plugin.name();
+
+            if names.insert(name.clone()) {
+                plugins.push(Resolved {
+                    enabled: config.apply.is_enabled(&name),
+                    config: plugin,
+                    source: Source::Discovered(dir),
+                });
+            } else {
+                debug!(
+                    "Ignoring {} because a plugin named {} already exists",
+                    plugin.executable.display(),
+                    name
+                );
+            }
+        }
+    }
+
+    plugins
+}
+
+/// Directories searched for plugins, in order of precedence.
+fn search_path(paths: &Paths) -> Vec<PathBuf> {
+    std::iter::once(paths.plugins.clone())
+        .chain(
+            std::env::var_os("PATH")
+                .map(|path| std::env::split_paths(&path).collect::<Vec<_>>())
+                .unwrap_or_default(),
+        )
+        .collect()
+}
+
+/// Find plugin executables in each directory, paired with the directory they were found in.
+fn discover(dirs: &[PathBuf]) -> Vec<(PathBuf, PathBuf)> {
+    let mut found = Vec::new();
+
+    for dir in dirs {
+        trace!("Searching {} for plugins...", dir.display());
+        let mut executables: Vec<_> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with(EXECUTABLE_PREFIX))
+                })
+                .filter(|path| check_executable(path).is_ok())
+                .collect(),
+            Err(_) => continue,
+        };
+        executables.sort();
+
+        found.extend(executables.into_iter().map(|exe| (dir.clone(), exe)));
+    }
+
+    found
+}
+
+/// Check that the plugin executable exists and can be run, explaining why not otherwise.
+pub fn check_executable(path: &Path) -> Result<(), String> {
+    let metadata = fs::metadata(path)
+        .map_err(|_| format!("plugin executable {} does not exist", path.display()))?;
+
+    if !metadata.is_file() {
+        return Err(format!(
+            "plugin executable {} is not a file",
+            path.display()
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Err(format!(
+                "plugin executable {} is not executable",
+                path.display()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Configuration of a discovered plugin, which runs with no arguments or options.
+fn discovered_config(executable: PathBuf) -> PluginConfig {
+    PluginConfig {
+        executable,
+        name: None,
+        kind: Default::default(),
+        args: Vec::new(),
+        tags: Vec::new(),
+        after: Vec::new(),
+        timeout: None,
+        protocol: None,
+        env: Default::default(),
+        overrides: Default::default(),
+        options: serde_json::Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn discover() {
+        let root = TempDir::new("discover");
+        let (first, second) = (root.join("first"), root.join("second"));
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+
+        let exe = |path: PathBuf| {
+            fs::write(&path, "#!/bin/sh\n").unwrap();
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            }
+            path
+        };
+        let first_foo = exe(first.join("luthien-foo"));
+        exe(second.join("luthien-foo"));
+        let second_bar = exe(second.join("luthien-bar"));
+        exe(second.join("unrelated"));
+        fs::write(second.join("luthien-data"), "").unwrap();
+
+        let found = super::discover(&[first.clone(), second.clone()]);
+
+        assert_eq!(found[0], (first, first_foo));
+        assert_eq!(found[1], (second.clone(), second_bar));
+        #[cfg(unix)]
+        assert_eq!(found.len(), 3, "{:#?}", found);
+        assert_eq!(discovered_config(found[0].1.clone()).name(), "foo");
+    }
+
+    #[test]
+    fn missing_executable() {
+        assert!(check_executable(Path::new("/non/existent/path")).is_err());
+        assert!(check_executable(Path::new("/")).is_err());
+    }
+}
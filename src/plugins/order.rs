@@ -0,0 +1,122 @@
+use crate::persist::{PluginConfig, PluginKind};
+use crate::plugin::Plugin;
+use color_eyre::eyre::{eyre, Result};
+
+/// Check that every dependency of the plugins is one of the `known` plugin names.
+pub fn check_dependencies(plugins: &[PluginConfig], known: &[String]) -> Result<()> {
+    for pl in plugins {
+        if let Some(dep) = pl.after.iter().find(|dep| !known.contains(dep)) {
+            return Err(eyre!(
+                "Plugin {} runs after {}, but there is no plugin with that name",
+                pl.name(),
+                dep
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that transforms only run after other transforms, given each plugin's dependencies as
+/// found by [`dependencies`].
+pub fn check_transforms(plugins: &[PluginConfig], deps: &[Vec<usize>]) -> Result<()> {
+    for (pl, deps) in plugins.iter().zip(deps) {
+        if pl.kind != PluginKind::Transform {
+            continue;
+        }
+        if let Some(dep) = deps
+            .iter()
+            .map(|&d| &plugins[d])
+            .find(|dep| dep.kind != PluginKind::Transform)
+        {
+            return Err(eyre!(
+                "Transform plugin {} can't run after {}, because transforms run before other plugins",
+                pl.name(),
+                dep.name()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Indices of each plugin's dependencies within `plugins`.
+///
+/// Dependencies which aren't in `plugins` because they're disabled or weren't selected are ignored,
+/// so unknown names should be rejected with [`check_dependencies`] first.
+pub fn dependencies(plugins: &[PluginConfig]) -> Vec<Vec<usize>> {
+    let names: Vec<_> = plugins.iter().map(Plugin::name).collect();
+
+    plugins
+        .iter()
+        .map(|pl| {
+            pl.after
+                .iter()
+                .filter_map(|dep| names.iter().position(|name| name == dep))
+                .collect()
+        })
+        .collect()
+}
+
+/// Order plugins so that each comes after its dependencies, otherwise keeping their order.
+///
+/// If the dependencies form a cycle, the indices of the plugins in the cycle are returned instead.
+pub fn topological_order(deps: &[Vec<usize>]) -> Result<Vec<usize>, Vec<usize>> {
+    let mut order = Vec::with_capacity(deps.len());
+    let mut done = vec![false; deps.len()];
+
+    while order.len() < deps.len() {
+        match (0..deps.len()).find(|&i| !done[i] && deps[i].iter().all(|&d| done[d])) {
+            Some(i) => {
+                done[i] = true;
+                order.push(i);
+            }
+            None => return Err(find_cycle(deps, &done)),
+        }
+    }
+
+    Ok(order)
+}
+
+/// Find a cycle among the plugins which couldn't be ordered.
+///
+/// Each of them has at least one dependency which couldn't be ordered either, so following those
+/// dependencies must eventually revisit a plugin.
+fn find_cycle(deps: &[Vec<usize>], done: &[bool]) -> Vec<usize> {
+    let mut path = Vec::new();
+    let mut current = (0..deps.len())
+        .find(|&i| !done[i])
+        .expect("All plugins were ordered");
+
+    loop {
+        if let Some(start) = path.iter().position(|&i| i == current) {
+            path.drain(..start);
+            return path;
+        }
+
+        path.push(current);
+        current = *deps[current]
+            .iter()
+            .find(|&&d| !done[d])
+            .expect("Unordered plugin has no unordered dependencies");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn topological_order() {
+        assert_eq!(
+            super::topological_order(&[vec![2], vec![], vec![1]]),
+            Ok(vec![1, 2, 0])
+        );
+        assert_eq!(
+            super::topological_order(&[vec![], vec![], vec![]]),
+            Ok(vec![0, 1, 2])
+        );
+        assert_eq!(
+            super::topological_order(&[vec![], vec![3], vec![1], vec![2], vec![3]]),
+            Err(vec![1, 3, 2])
+        );
+    }
+}
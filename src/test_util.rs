@@ -0,0 +1,126 @@
+//! Helpers shared by the unit tests.
+
+use crate::theme::{Colors, Palette, Theme};
+use palette::Srgb;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// A theme whose palette has fully saturated colors, which are also its accents, and whose
+/// wallpaper is `test.jpg`.
+pub fn theme() -> Theme {
+    let palette = Palette {
+        black: Srgb::new(0.0, 0.0, 0.0),
+        red: Srgb::new(1.0, 0.0, 0.0),
+        green: Srgb::new(0.0, 1.0, 0.0),
+        yellow: Srgb::new(1.0, 1.0, 0.0),
+        blue: Srgb::new(0.0, 0.0, 1.0),
+        purple: Srgb::new(1.0, 0.0, 1.0),
+        cyan: Srgb::new(0.0, 1.0, 1.0),
+        white: Srgb::new(1.0, 1.0, 1.0),
+    };
+
+    Theme {
+        wallpaper: Some(PathBuf::from("test.jpg")),
+        colors: Colors {
+            accents: palette.accents().to_vec(),
+            foreground: palette.white,
+            background: palette.black,
+            palette,
+        },
+    }
+}
+
+/// An empty temporary directory, which is removed when dropped even if the test panics.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create the directory; `name` must be unique among the tests.
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("luthien_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Variables which locate the user's configuration, data, and cache directories.
+const HOME_VARS: [&str; 3] = ["XDG_CONFIG_HOME", "XDG_DATA_HOME", "XDG_CACHE_HOME"];
+
+/// Serializes the tests which change or read the environment, since it's shared by every test.
+static HOME_LOCK: Mutex<()> = Mutex::new(());
+
+/// Lock the environment, so that a [`Home`] can't redirect the user's directories while a test
+/// is reading them.
+pub fn lock_env() -> MutexGuard<'static, ()> {
+    HOME_LOCK.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Redirects the user's configuration, data, and cache directories to a temporary directory until
+/// it's dropped.
+///
+/// Running a plugin creates its directories, so tests which run plugins use this to stay out of
+/// the real home directory.
+pub struct Home {
+    previous: Vec<(&'static str, Option<OsString>)>,
+    dir: TempDir,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Home {
+    pub fn new(name: &str) -> Self {
+        let lock = lock_env();
+        let dir = TempDir::new(name);
+        let previous = HOME_VARS
+            .iter()
+            .map(|&var| {
+                let previous = env::var_os(var);
+                env::set_var(var, dir.join(var.to_lowercase()));
+                (var, previous)
+            })
+            .collect();
+
+        Self {
+            previous,
+            dir,
+            _lock: lock,
+        }
+    }
+}
+
+impl Deref for Home {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for Home {
+    fn drop(&mut self) {
+        for (var, previous) in self.previous.drain(..) {
+            match previous {
+                Some(value) => env::set_var(var, value),
+                None => env::remove_var(var),
+            }
+        }
+    }
+}
@@ -1,7 +1,8 @@
+use color_eyre::eyre::{Result, WrapErr};
 use palette::{IntoColor, Srgb};
 use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Palette<T> {
@@ -85,6 +86,20 @@ impl<T> Palette<T> {
     }
 }
 
+impl Palette<&'static str> {
+    /// The name of each color in the palette.
+    pub const NAMES: Self = Self {
+        black: "black",
+        red: "red",
+        green: "green",
+        yellow: "yellow",
+        blue: "blue",
+        purple: "purple",
+        cyan: "cyan",
+        white: "white",
+    };
+}
+
 impl<T> fmt::Display for Palette<T>
 where
     T: IntoColor + Clone,
@@ -124,6 +139,28 @@ impl<T> Colors<T> {
     }
 }
 
+impl Colors {
+    /// The color with the given name: `foreground`, `background`, a palette color, or `accent_N`.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Srgb> {
+        match name {
+            "foreground" => Some(&mut self.foreground),
+            "background" => Some(&mut self.background),
+            "black" => Some(&mut self.palette.black),
+            "red" => Some(&mut self.palette.red),
+            "green" => Some(&mut self.palette.green),
+            "yellow" => Some(&mut self.palette.yellow),
+            "blue" => Some(&mut self.palette.blue),
+            "purple" => Some(&mut self.palette.purple),
+            "cyan" => Some(&mut self.palette.cyan),
+            "white" => Some(&mut self.palette.white),
+            _ => name
+                .strip_prefix("accent_")
+                .and_then(|i| i.parse::<usize>().ok())
+                .and_then(move |i| self.accents.get_mut(i)),
+        }
+    }
+}
+
 impl<T> fmt::Display for Colors<T>
 where
     T: IntoColor + Clone,
@@ -150,6 +187,28 @@ pub struct Theme {
     pub colors: Colors,
 }
 
+impl Theme {
+    /// Resolve a relative wallpaper path relative to `dir`, rather than the working directory.
+    pub fn resolve_wallpaper(&mut self, dir: &Path) {
+        if let Some(wallpaper) = &mut self.wallpaper {
+            if wallpaper.is_relative() {
+                *wallpaper = dir.join(&wallpaper);
+            }
+        }
+    }
+
+    /// Make the wallpaper path absolute, failing if the wallpaper doesn't exist.
+    pub fn canonicalize_wallpaper(&mut self) -> Result<()> {
+        if let Some(wallpaper) = &mut self.wallpaper {
+            *wallpaper = wallpaper
+                .canonicalize()
+                .wrap_err_with(|| format!("The wallpaper {} doesn't exist", wallpaper.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
 impl fmt::Display for Theme {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(bg) = &self.wallpaper {
@@ -159,6 +218,32 @@ impl fmt::Display for Theme {
     }
 }
 
+/// Hexadecimal code of a color, like `#ff8000`.
+pub fn hex(color: Srgb) -> String {
+    let byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        byte(color.red),
+        byte(color.green),
+        byte(color.blue)
+    )
+}
+
+/// Parse a hexadecimal color code like `#ff8000`. The leading `#` is optional.
+pub fn parse_hex(hex: &str) -> Option<Srgb> {
+    let hex = hex.trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .ok()
+            .map(|c| c as f32 / 255.0)
+    };
+    Some(Srgb::new(channel(0)?, channel(2)?, channel(4)?))
+}
+
 fn color_block<T: IntoColor + Clone>(col: T) -> impl fmt::Display {
     use colored::*;
 
@@ -218,9 +303,36 @@ mod tests {
         assert_eq!(test_theme!(), deserialized);
     }
 
+    #[test]
+    fn hex() {
+        assert_eq!(super::hex(Srgb::new(1.0, 0.5, 0.0)), "#ff8000");
+        assert_eq!(super::hex(Srgb::new(-0.1, 0.0, 1.1)), "#0000ff");
+    }
+
+    #[test]
+    fn parse_hex() {
+        assert_eq!(super::parse_hex("#ff0000"), Some(Srgb::new(1.0, 0.0, 0.0)));
+        assert_eq!(super::parse_hex("00ff00"), Some(Srgb::new(0.0, 1.0, 0.0)));
+        assert_eq!(super::parse_hex("#ff00"), None);
+        assert_eq!(super::parse_hex("#gg0000"), None);
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut theme = test_theme!();
+        *theme.colors.get_mut("background").unwrap() = Srgb::new(0.5, 0.5, 0.5);
+        assert_eq!(theme.colors.background, Srgb::new(0.5, 0.5, 0.5));
+        assert_eq!(
+            theme.colors.get_mut("accent_3").copied(),
+            Some(Srgb::new(0.0, 0.0, 1.0))
+        );
+        assert_eq!(theme.colors.get_mut("accent_6"), None);
+        assert_eq!(theme.colors.get_mut("orange"), None);
+    }
+
     #[test]
     fn display() {
-        format!("{}", test_theme!().colors.palette);
-        format!("{}", test_theme!());
+        let _ = format!("{}", test_theme!().colors.palette);
+        let _ = format!("{}", test_theme!());
     }
 }
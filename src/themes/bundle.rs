@@ -0,0 +1,164 @@
+use crate::persist::Paths;
+use crate::theme::Theme;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use log::trace;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Name of the theme file within a bundle.
+const THEME_ENTRY: &str = "theme.json";
+
+/// Write the theme and its wallpaper to a bundle, which is a tar archive.
+///
+/// The bundled theme refers to the wallpaper by its name within the bundle, so that bundles can be
+/// shared between machines.
+pub fn export(theme: &Theme, output: &Path) -> Result<()> {
+    let mut builder =
+        tar::Builder::new(File::create(output).wrap_err("Failed to create bundle file")?);
+    let mut theme = theme.clone();
+
+    if let Some(wallpaper) = &theme.wallpaper {
+        let entry = match wallpaper.extension() {
+            Some(ext) => format!("wallpaper.{}", ext.to_string_lossy()),
+            None => "wallpaper".into(),
+        };
+
+        trace!("Adding wallpaper to bundle...");
+        builder
+            .append_path_with_name(wallpaper, &entry)
+            .wrap_err_with(|| format!("Failed to add {} to the bundle", wallpaper.display()))?;
+        theme.wallpaper = Some(entry.into());
+    }
+
+    trace!("Adding theme to bundle...");
+    let json = serde_json::to_vec_pretty(&theme).wrap_err("Failed to serialize the theme")?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(json.len() as u64);
+    header.set_mode(0o644);
+    builder
+        .append_data(&mut header, THEME_ENTRY, json.as_slice())
+        .wrap_err("Failed to add the theme to the bundle")?;
+
+    builder
+        .into_inner()
+        .wrap_err("Failed to finish writing the bundle")?;
+    Ok(())
+}
+
+/// Save the theme in a bundle to the theme library, with its wallpaper alongside it.
+///
+/// Only the theme and the wallpaper it refers to are read from the bundle; other files are ignored.
+/// An existing theme with the same name is only replaced if `force` is set.
+pub fn import(paths: &Paths, bundle: &Path, name: &str, force: bool) -> Result<PathBuf> {
+    if paths.theme_path(name)?.exists() && !force {
+        return Err(eyre!(
+            "A theme named \"{}\" already exists; use --force to replace it",
+            name
+        ));
+    }
+
+    let mut files = HashMap::new();
+    let mut archive = tar::Archive::new(File::open(bundle).wrap_err("Failed to open bundle")?);
+    for entry in archive.entries().wrap_err("Failed to read bundle")? {
+        let mut entry = entry.wrap_err("Failed to read bundle entry")?;
+        let path = entry
+            .path()
+            .wrap_err("Bundle entry has an invalid path")?
+            .into_owned();
+
+        let mut contents = Vec::new();
+        entry
+            .read_to_end(&mut contents)
+            .wrap_err_with(|| format!("Failed to read {} from bundle", path.display()))?;
+        files.insert(path, contents);
+    }
+
+    let mut theme: Theme = serde_json::from_slice(
+        files
+            .get(Path::new(THEME_ENTRY))
+            .ok_or_else(|| eyre!("The bundle doesn't contain a theme"))?,
+    )
+    .wrap_err("Failed to deserialize the bundled theme")?;
+
+    if let Some(wallpaper) = theme.wallpaper.take() {
+        let contents = files.get(&wallpaper).ok_or_else(|| {
+            eyre!(
+                "The bundle doesn't contain its wallpaper, {}",
+                wallpaper.display()
+            )
+        })?;
+
+        // Named after its contents, so that themes which are renamed or deleted never share a
+        // wallpaper file with a different image. Relative to the theme file, so that the theme
+        // library can be moved.
+        let mut relative = Path::new("wallpapers").join(format!("{:016x}", content_hash(contents)));
+        if let Some(ext) = wallpaper.extension() {
+            relative.set_extension(ext);
+        }
+        let path = paths.themes.join(&relative);
+        fs::create_dir_all(paths.themes.join("wallpapers"))
+            .wrap_err("Failed to create wallpapers directory")?;
+
+        trace!("Writing wallpaper to {}...", path.display());
+        fs::write(&path, contents).wrap_err("Failed to write the bundled wallpaper")?;
+        theme.wallpaper = Some(relative);
+    }
+
+    paths.save_theme(name, &theme, force)
+}
+
+/// 64-bit FNV-1a hash of `bytes`, which unlike the standard library's hashers is the same in every
+/// build, so that importing the same wallpaper again reuses its file.
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, TempDir};
+
+    #[test]
+    fn round_trip() {
+        let _env = test_util::lock_env();
+        let root = TempDir::new("bundle");
+        let paths = Paths {
+            themes: root.join("themes"),
+            ..Paths::default()
+        };
+        fs::create_dir_all(&paths.themes).unwrap();
+        fs::write(root.join("beach.png"), b"not really a png").unwrap();
+
+        let theme = Theme {
+            wallpaper: Some(root.join("beach.png")),
+            ..test_util::theme()
+        };
+        export(&theme, &root.join("bundle.tar")).unwrap();
+        let imported = import(&paths, &root.join("bundle.tar"), "beach", false).unwrap();
+        let replaced = import(&paths, &root.join("bundle.tar"), "beach", false);
+
+        // A different wallpaper imported under another name doesn't replace the first one.
+        fs::write(root.join("beach.png"), b"another image").unwrap();
+        export(&theme, &root.join("other.tar")).unwrap();
+        let other = import(&paths, &root.join("other.tar"), "other", false).unwrap();
+
+        let imported = paths.get_theme(imported).unwrap();
+        let other = paths.get_theme(other).unwrap();
+        let wallpaper = fs::read(imported.wallpaper.as_ref().unwrap()).unwrap();
+        let forced = import(&paths, &root.join("other.tar"), "beach", true);
+
+        assert!(replaced.is_err());
+        assert!(forced.is_ok());
+        assert_eq!(
+            imported.wallpaper,
+            Some(paths.themes.join("wallpapers/ddba65005ed08c39.png"))
+        );
+        assert_ne!(imported.wallpaper, other.wallpaper);
+        assert_eq!(wallpaper, b"not really a png");
+        assert_eq!(imported.colors, theme.colors);
+    }
+}
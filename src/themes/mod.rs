@@ -0,0 +1,159 @@
+mod bundle;
+
+use crate::apply;
+use crate::persist::{Config, Paths};
+use crate::theme::Theme;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use log::info;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, PartialEq, Clone, StructOpt)]
+pub enum Opt {
+    /// Save a theme to the theme library.
+    Save {
+        /// Name to save the theme as.
+        name: String,
+
+        /// Theme to save. Defaults to the last applied theme.
+        theme: Option<PathBuf>,
+
+        /// Replace an existing theme with the same name.
+        #[structopt(short, long)]
+        force: bool,
+    },
+
+    /// List the themes in the theme library.
+    #[structopt(aliases = &["ls"])]
+    List,
+
+    /// Show a preview of a saved theme.
+    Show {
+        /// Name of the theme.
+        name: String,
+    },
+
+    /// Rename a saved theme.
+    #[structopt(aliases = &["mv"])]
+    Rename {
+        /// Current name of the theme.
+        from: String,
+
+        /// New name of the theme.
+        to: String,
+    },
+
+    /// Export a theme and its wallpaper to a bundle which can be shared.
+    Export {
+        /// Name of the theme.
+        name: String,
+
+        /// Path of the bundle. Defaults to the theme's name with a `.tar` extension.
+        #[structopt(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Import a theme bundle into the theme library.
+    Import {
+        /// Path of the bundle.
+        bundle: PathBuf,
+
+        /// Name to save the theme as. Defaults to the bundle's file name.
+        #[structopt(short, long)]
+        name: Option<String>,
+
+        /// Replace an existing theme with the same name.
+        #[structopt(short, long)]
+        force: bool,
+    },
+
+    /// Delete a saved theme.
+    #[structopt(aliases = &["rm"])]
+    Delete {
+        /// Name of the theme.
+        name: String,
+    },
+}
+
+impl crate::Command for Opt {
+    fn run(self, paths: &Paths, _config: &Config) -> Result<Option<Theme>> {
+        match self {
+            Self::Save { name, theme, force } => {
+                let theme = match theme {
+                    Some(path) => paths.get_theme(path)?,
+                    None => apply::last_applied(paths)?,
+                };
+                let path = paths.save_theme(&name, &theme, force)?;
+                info!("Saved theme to {}", path.display());
+            }
+            Self::List => {
+                let names = paths.theme_names()?;
+                if names.is_empty() {
+                    info!("No themes have been saved");
+                }
+
+                for name in names {
+                    println!("{}", name);
+                    match paths.get_theme(paths.theme_path(&name)?) {
+                        Ok(theme) => println!("  {}", theme.to_string().replace('\n', "\n  ")),
+                        Err(err) => println!("  Invalid theme: {}", err.root_cause()),
+                    }
+                }
+            }
+            Self::Show { name } => {
+                println!("{}", paths.get_theme(saved(paths, &name)?)?);
+            }
+            Self::Rename { from, to } => {
+                let (from_path, to_path) = (saved(paths, &from)?, paths.theme_path(&to)?);
+                if to_path.exists() {
+                    return Err(eyre!("A theme named \"{}\" already exists", to));
+                }
+
+                fs::rename(from_path, to_path).wrap_err("Failed to rename theme")?;
+                info!("Renamed theme {} to {}", from, to);
+            }
+            Self::Export { name, output } => {
+                let output = output.unwrap_or_else(|| {
+                    PathBuf::from(name.strip_suffix(".json").unwrap_or(&name)).with_extension("tar")
+                });
+                bundle::export(&paths.get_theme(saved(paths, &name)?)?, &output)
+                    .wrap_err("Failed to export theme")?;
+                info!("Exported theme to {}", output.display());
+            }
+            Self::Import {
+                bundle,
+                name,
+                force,
+            } => {
+                let name = match name {
+                    Some(name) => name,
+                    None => bundle
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .ok_or_else(|| eyre!("Failed to name the theme; use --name"))?
+                        .into(),
+                };
+                let path = bundle::import(paths, &bundle, &name, force)
+                    .wrap_err("Failed to import theme")?;
+                info!("Imported theme to {}", path.display());
+            }
+            Self::Delete { name } => {
+                fs::remove_file(saved(paths, &name)?).wrap_err("Failed to delete theme")?;
+                info!("Deleted theme {}", name);
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Path of a theme in the theme library, which must exist.
+fn saved(paths: &Paths, name: &str) -> Result<PathBuf> {
+    let path = paths.theme_path(name)?;
+    if !path.exists() {
+        return Err(eyre!("No saved theme named \"{}\"", name));
+    }
+
+    Ok(path)
+}